@@ -0,0 +1,293 @@
+use std::collections::BTreeMap;
+
+use osm_reader::{NodeID, WayID};
+use serde::Serialize;
+
+use crate::{
+    Kind, Node, Problem, ProblemType, Speedwalk,
+    audit::Options,
+    graph::Graph,
+    problems::{Category, ProblemScope},
+};
+
+/// Tags wheelchair users need on every crossing, and the problem when one is missing
+const REQUIRED_KEYS: [(&str, ProblemType); 3] = [
+    ("kerb", ProblemType::CrossingMissingKerb),
+    ("tactile_paving", ProblemType::CrossingMissingTactilePaving),
+    ("crossing:markings", ProblemType::CrossingMissingMarkings),
+];
+
+/// A crossing way, or a crossing node on a road that isn't part of one
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Crossing {
+    Node(NodeID),
+    Way(WayID),
+}
+
+pub(crate) struct AccessibilityIssue {
+    pub crossing: Crossing,
+    pub problem_type: ProblemType,
+    /// Steps connected to the crossing
+    pub steps: Vec<WayID>,
+}
+
+/// Accessibility problem counts over the whole area, and for each junction
+#[derive(Default, Serialize)]
+pub(crate) struct AccessibilityMetrics {
+    crossings: usize,
+    problems: BTreeMap<&'static str, usize>,
+    junctions: Vec<JunctionAccessibility>,
+}
+
+#[derive(Serialize)]
+struct JunctionAccessibility {
+    /// WGS84
+    point: [f64; 2],
+    crossings: usize,
+    problems: BTreeMap<&'static str, usize>,
+}
+
+impl Speedwalk {
+    /// Turn the accessibility checks on or off. They're off by default.
+    pub fn set_accessibility_checks(&mut self, enabled: bool) {
+        if self.check_accessibility != enabled {
+            self.check_accessibility = enabled;
+            self.recalculate_problems();
+        }
+    }
+
+    /// Crossings in scope missing tags wheelchair users need, or connected to steps. Sorted by
+    /// crossing.
+    pub(crate) fn find_accessibility_issues(
+        &self,
+        scope: Option<&ProblemScope>,
+    ) -> Vec<AccessibilityIssue> {
+        let mut results = Vec::new();
+        for crossing in self.crossings() {
+            let in_scope = match crossing {
+                Crossing::Node(id) => scope.is_none_or(|s| s.has_node(&self.derived_nodes[&id])),
+                Crossing::Way(id) => scope.is_none_or(|s| s.has_way(&self.derived_ways[&id])),
+            };
+            if !in_scope {
+                continue;
+            }
+
+            let nodes = self.crossing_nodes(crossing);
+            for (key, problem_type) in REQUIRED_KEYS {
+                let mut tagged = nodes.iter().any(|n| n.tags.has(key));
+                if let Crossing::Way(id) = crossing {
+                    tagged |= self.derived_ways[&id].tags.has(key);
+                }
+                if !tagged {
+                    results.push(AccessibilityIssue {
+                        crossing,
+                        problem_type,
+                        steps: Vec::new(),
+                    });
+                }
+            }
+
+            let mut steps: Vec<WayID> = nodes
+                .iter()
+                .flat_map(|n| n.way_ids.iter())
+                .filter(|w| self.derived_ways[w].tags.is("highway", "steps"))
+                .cloned()
+                .collect();
+            steps.sort();
+            steps.dedup();
+            if !steps.is_empty() {
+                results.push(AccessibilityIssue {
+                    crossing,
+                    problem_type: ProblemType::CrossingConnectsViaSteps,
+                    steps,
+                });
+            }
+        }
+        results
+    }
+
+    /// Counts unsuppressed accessibility problems. Junctions come from the crossing audit with
+    /// default options.
+    pub(crate) fn accessibility_metrics(&self) -> AccessibilityMetrics {
+        let mut metrics = AccessibilityMetrics::default();
+        for crossing in self.crossings() {
+            metrics.crossings += 1;
+            count_problems(&mut metrics.problems, self.crossing_problems(crossing));
+        }
+
+        let graph = Graph::new(self);
+        for junction in self.find_junctions(&Options::default(), &graph) {
+            let pt = self.mercator.pt_to_wgs84(junction.centroid.into());
+            let mut result = JunctionAccessibility {
+                point: [pt.x, pt.y],
+                crossings: 0,
+                problems: BTreeMap::new(),
+            };
+            for node_id in &junction.crossings {
+                // Count the crossing ways through the node instead, if there are any
+                let mut crossings: Vec<Crossing> = self.derived_nodes[node_id]
+                    .way_ids
+                    .iter()
+                    .filter(|w| self.derived_ways[w].kind == Kind::Crossing)
+                    .map(|w| Crossing::Way(*w))
+                    .collect();
+                if crossings.is_empty() {
+                    crossings.push(Crossing::Node(*node_id));
+                }
+                for crossing in crossings {
+                    result.crossings += 1;
+                    count_problems(&mut result.problems, self.crossing_problems(crossing));
+                }
+            }
+            metrics.junctions.push(result);
+        }
+        metrics
+    }
+
+    /// Every crossing way, then every crossing node on a road not already part of a crossing way.
+    /// Sorted by ID.
+    fn crossings(&self) -> Vec<Crossing> {
+        let mut ways: Vec<WayID> = self
+            .derived_ways
+            .iter()
+            .filter(|(_, way)| way.kind == Kind::Crossing)
+            .map(|(id, _)| *id)
+            .collect();
+        ways.sort();
+
+        let mut nodes: Vec<NodeID> = self
+            .derived_nodes
+            .iter()
+            .filter(|(_, node)| {
+                node.is_crossing()
+                    && node
+                        .way_ids
+                        .iter()
+                        .any(|w| self.derived_ways[w].kind.is_road())
+                    && !node
+                        .way_ids
+                        .iter()
+                        .any(|w| self.derived_ways[w].kind == Kind::Crossing)
+            })
+            .map(|(id, _)| *id)
+            .collect();
+        nodes.sort();
+
+        ways.into_iter()
+            .map(Crossing::Way)
+            .chain(nodes.into_iter().map(Crossing::Node))
+            .collect()
+    }
+
+    /// Nodes whose tags describe the crossing. Kerbs and tactile paving are often mapped on the
+    /// ends of a crossing way.
+    fn crossing_nodes(&self, crossing: Crossing) -> Vec<&Node> {
+        match crossing {
+            Crossing::Node(id) => vec![&self.derived_nodes[&id]],
+            Crossing::Way(id) => self.derived_ways[&id]
+                .node_ids
+                .iter()
+                .map(|n| &self.derived_nodes[n])
+                .collect(),
+        }
+    }
+
+    fn crossing_problems(&self, crossing: Crossing) -> &Vec<Problem> {
+        match crossing {
+            Crossing::Node(id) => &self.derived_nodes[&id].problems,
+            Crossing::Way(id) => &self.derived_ways[&id].problems,
+        }
+    }
+}
+
+fn count_problems(counts: &mut BTreeMap<&'static str, usize>, problems: &[Problem]) {
+    for problem in problems {
+        if matches!(problem.problem_type.category(), Category::Accessibility) && !problem.suppressed
+        {
+            *counts.entry(problem.problem_type.code()).or_default() += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accessibility_issues() {
+        // Crossing way 200 has kerbs on both ends and reaches steps. Crossing node 6 on road 101
+        // has nothing.
+        let osm = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <node id="1" lon="0.000000" lat="0.000000" version="1" />
+  <node id="2" lon="0.000450" lat="0.000000" version="1">
+    <tag k="highway" v="crossing"/>
+    <tag k="crossing" v="uncontrolled"/>
+    <tag k="crossing:markings" v="zebra"/>
+  </node>
+  <node id="3" lon="0.000900" lat="0.000000" version="1" />
+  <node id="4" lon="0.000450" lat="-0.000100" version="1">
+    <tag k="barrier" v="kerb"/>
+    <tag k="kerb" v="lowered"/>
+  </node>
+  <node id="5" lon="0.000450" lat="0.000100" version="1">
+    <tag k="barrier" v="kerb"/>
+    <tag k="kerb" v="raised"/>
+  </node>
+  <node id="6" lon="0.000450" lat="0.001000" version="1">
+    <tag k="highway" v="crossing"/>
+  </node>
+  <node id="7" lon="0.000000" lat="0.001000" version="1" />
+  <node id="8" lon="0.000900" lat="0.001000" version="1" />
+  <node id="9" lon="0.000450" lat="0.000200" version="1" />
+  <way id="100" version="1">
+    <nd ref="1"/><nd ref="2"/><nd ref="3"/>
+    <tag k="highway" v="residential"/>
+  </way>
+  <way id="101" version="1">
+    <nd ref="7"/><nd ref="6"/><nd ref="8"/>
+    <tag k="highway" v="residential"/>
+  </way>
+  <way id="200" version="1">
+    <nd ref="4"/><nd ref="2"/><nd ref="5"/>
+    <tag k="highway" v="footway"/>
+    <tag k="footway" v="crossing"/>
+  </way>
+  <way id="201" version="1">
+    <nd ref="5"/><nd ref="9"/>
+    <tag k="highway" v="steps"/>
+  </way>
+</osm>"#;
+        let mut model = Speedwalk::new_from_osm(osm.as_bytes(), None).unwrap();
+        // Off by default
+        assert!(model.derived_ways[&WayID(200)].problems.is_empty());
+        assert!(model.derived_nodes[&NodeID(6)].problems.is_empty());
+
+        model.set_accessibility_checks(true);
+        let found: Vec<_> = model
+            .find_accessibility_issues(None)
+            .into_iter()
+            .map(|x| (x.crossing, x.problem_type.code()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (Crossing::Way(WayID(200)), "crossing_missing_tactile_paving"),
+                (Crossing::Way(WayID(200)), "crossing_connects_via_steps"),
+                (Crossing::Node(NodeID(6)), "crossing_missing_kerb"),
+                (Crossing::Node(NodeID(6)), "crossing_missing_tactile_paving"),
+                (Crossing::Node(NodeID(6)), "crossing_missing_markings"),
+            ]
+        );
+        assert_eq!(model.derived_ways[&WayID(200)].problems.len(), 2);
+        assert_eq!(model.derived_nodes[&NodeID(6)].problems.len(), 3);
+
+        let metrics = model.accessibility_metrics();
+        assert_eq!(metrics.crossings, 2);
+        assert_eq!(metrics.problems["crossing_missing_tactile_paving"], 2);
+        assert_eq!(metrics.problems["crossing_connects_via_steps"], 1);
+
+        model.set_accessibility_checks(false);
+        assert!(model.derived_nodes[&NodeID(6)].problems.is_empty());
+    }
+}
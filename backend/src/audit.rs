@@ -1,19 +1,33 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
 use anyhow::Result;
 use geo::{Distance, Euclidean, InterpolatableLine, InterpolatePoint, LineString, Point};
 use geojson::GeoJson;
-use osm_reader::NodeID;
-use serde::Deserialize;
+use osm_reader::{NodeID, WayID};
+use serde::{Deserialize, Serialize};
 use utils::Tags;
 
 use crate::{
-    Speedwalk, UserCmd, Way,
+    Kind, Node, Side, Speedwalk, UserCmd, Way,
     crossings::shortest_rotation,
     graph::{EdgeID, Graph, IntersectionID},
+    make_sidewalks::side_tagged_yes,
 };
 
-#[derive(Deserialize)]
+/// How much a crossing on a junction arm counts towards the junction's score, by how it's
+/// controlled
+const SCORE_TRAFFIC_SIGNALS: f64 = 1.0;
+const SCORE_UNCONTROLLED: f64 = 0.8;
+const SCORE_UNMARKED: f64 = 0.6;
+const SCORE_UNKNOWN_CONTROL: f64 = 0.5;
+/// A crossing that doesn't connect sidewalks on both sides only counts this much
+const DISCONNECTED_FACTOR: f64 = 0.5;
+/// A crossing farther than this from the middle of the junction is a detour...
+const FAR_CROSSING_DISTANCE: f64 = 20.0;
+/// ...and only counts this much
+const FAR_CROSSING_FACTOR: f64 = 0.8;
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Options {
     only_major_roads: bool,
     ignore_utility_roads: bool,
@@ -24,16 +38,34 @@ pub struct Options {
     max_distance: f64,
 }
 
+/// Matches the defaults in the crossing audit UI
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            only_major_roads: true,
+            ignore_utility_roads: true,
+            ignore_cycleways: true,
+            ignore_footways: true,
+            ignore_roundabouts: true,
+            ignore_motorways: true,
+            max_distance: 40.0,
+        }
+    }
+}
+
 impl Speedwalk {
     pub fn audit_crossings(&self, options: Options) -> Result<String> {
         let mut features = Vec::new();
 
         let graph = Graph::new(self);
 
-        for junction in self.find_junctions(options, &graph) {
-            let mut f = self
-                .mercator
-                .to_wgs84_gj(&graph.intersections[&junction.i].point);
+        for junction in self.find_junctions(&options, &graph) {
+            let mut f = self.mercator.to_wgs84_gj(&junction.centroid);
+            f.set_property("roundabout", junction.is_roundabout());
+
+            let score = self.score_junction(&junction);
+            f.set_property("score", round2(score.score));
+            f.set_property("arm_scores", serde_json::to_value(&score.arms)?);
 
             let mut arms = Vec::new();
             for (_, ls, has_crossing) in junction.arms {
@@ -63,15 +95,12 @@ impl Speedwalk {
                 crossings.len() + explicit_non_crossings.len()
                     >= arms.len()
                         - junction.number_dual_carriageway_splits
-                        - junction.number_roundabout_arms
                         - junction.number_motorway_arms,
             );
             f.set_property("arms", GeoJson::from(arms));
             f.set_property(
                 "number_ignored_arms",
-                junction.number_dual_carriageway_splits
-                    + junction.number_roundabout_arms
-                    + junction.number_motorway_arms,
+                junction.number_dual_carriageway_splits + junction.number_motorway_arms,
             );
             f.set_property("crossings", GeoJson::from(crossings));
             f.set_property(
@@ -85,11 +114,140 @@ impl Speedwalk {
         Ok(serde_json::to_string(&GeoJson::from(features))?)
     }
 
+    /// Summarize junction crossing scores over the whole area
+    pub fn audit_crossings_summary(&self, options: Options) -> Result<String> {
+        let graph = Graph::new(self);
+        let mut summary = Summary {
+            timestamp: self.timestamp,
+            ..Default::default()
+        };
+        let mut total_score = 0.0;
+        for junction in self.find_junctions(&options, &graph) {
+            let score = self.score_junction(&junction);
+            summary.junctions += 1;
+            if score.score >= 1.0 {
+                summary.fully_scored_junctions += 1;
+            }
+            total_score += score.score;
+            for arm in score.arms {
+                match arm.coverage {
+                    "crossing" => {
+                        summary.arms_with_crossing += 1;
+                        *summary
+                            .crossings_by_control
+                            .entry(arm.control.unwrap())
+                            .or_default() += 1;
+                        if arm.connected == Some(true) {
+                            summary.connected_crossings += 1;
+                        }
+                    }
+                    "crossing=no" => summary.arms_with_crossing_no += 1,
+                    "missing" => summary.arms_missing += 1,
+                    _ => {}
+                }
+            }
+        }
+        if summary.junctions > 0 {
+            summary.mean_score = round2(total_score / summary.junctions as f64);
+        }
+        Ok(serde_json::to_string(&summary)?)
+    }
+
+    fn score_junction(&self, junction: &Junction) -> JunctionScore {
+        let junction_pt = junction.centroid;
+        let mut arms = Vec::new();
+        let mut total = 0.0;
+        let mut counted_arms: usize = 0;
+        for coverage in &junction.coverage {
+            let arm = match coverage {
+                ArmCoverage::Crossing(n) => {
+                    let node = &self.derived_nodes[n];
+                    let control = crossing_control(&node.tags);
+                    let distance = Euclidean.distance(junction_pt, Point::from(node.pt));
+                    let connected = self.crossing_connects_sidewalks(node);
+                    let mut score = match control {
+                        "traffic_signals" => SCORE_TRAFFIC_SIGNALS,
+                        "uncontrolled" => SCORE_UNCONTROLLED,
+                        "unmarked" => SCORE_UNMARKED,
+                        _ => SCORE_UNKNOWN_CONTROL,
+                    };
+                    if !connected {
+                        score *= DISCONNECTED_FACTOR;
+                    }
+                    if distance > FAR_CROSSING_DISTANCE {
+                        score *= FAR_CROSSING_FACTOR;
+                    }
+                    ArmScore {
+                        coverage: "crossing",
+                        node: Some(n.0),
+                        control: Some(control),
+                        distance: Some(round2(distance)),
+                        connected: Some(connected),
+                        score: Some(round2(score)),
+                    }
+                }
+                // Mapping that there's no crossing is complete
+                ArmCoverage::ExplicitNo(n) => ArmScore {
+                    coverage: "crossing=no",
+                    node: Some(n.0),
+                    score: Some(1.0),
+                    ..Default::default()
+                },
+                ArmCoverage::Ignored => ArmScore {
+                    coverage: "ignored",
+                    ..Default::default()
+                },
+                ArmCoverage::Missing => ArmScore {
+                    coverage: "missing",
+                    score: Some(0.0),
+                    ..Default::default()
+                },
+            };
+            if let Some(score) = arm.score {
+                total += score;
+                counted_arms += 1;
+            }
+            arms.push(arm);
+        }
+
+        // Each dual carriageway split is an arm not expected to have its own crossing
+        let expected_arms = counted_arms
+            .saturating_sub(junction.number_dual_carriageway_splits)
+            .max(1);
+        JunctionScore {
+            arms,
+            score: (total / expected_arms as f64).min(1.0),
+        }
+    }
+
+    /// Does a crossing node lead to sidewalks on both sides of the road?
+    fn crossing_connects_sidewalks(&self, node: &Node) -> bool {
+        node.way_ids.iter().any(|w| {
+            let way = &self.derived_ways[w];
+            // Sidewalks tagged on the road itself are on both sides of the crossing already
+            if way.kind == Kind::RoadWithTags {
+                return side_tagged_yes(&way.tags, Side::Left)
+                    && side_tagged_yes(&way.tags, Side::Right);
+            }
+            if way.kind != Kind::Crossing {
+                return false;
+            }
+            [way.node_ids[0], *way.node_ids.last().unwrap()]
+                .iter()
+                .all(|end| {
+                    self.derived_nodes[end]
+                        .way_ids
+                        .iter()
+                        .any(|other| other != w && !self.derived_ways[other].kind.is_road())
+                })
+        })
+    }
+
     pub fn generate_missing_crossings(&mut self, options: Options) -> Result<()> {
         let graph = Graph::new(self);
 
         let mut pts = Vec::new();
-        for junction in self.find_junctions(options, &graph) {
+        for junction in self.find_junctions(&options, &graph) {
             for (_, ls, has_crossing) in junction.arms {
                 if !has_crossing {
                     // Add the crossing close to the junction at a fixed position. Make it slightly
@@ -120,26 +278,61 @@ impl Speedwalk {
         Ok(())
     }
 
-    /// Find all junctions
-    fn find_junctions(&self, options: Options, graph: &Graph) -> Vec<Junction> {
+    /// Find all junctions. With `ignore_roundabouts`, each roundabout is one junction, with every
+    /// road entering or leaving it as an arm.
+    pub(crate) fn find_junctions(&self, options: &Options, graph: &Graph) -> Vec<Junction> {
+        let rings = if options.ignore_roundabouts {
+            self.find_roundabouts(graph)
+        } else {
+            Vec::new()
+        };
+        let on_ring: HashSet<IntersectionID> = rings
+            .iter()
+            .flat_map(|ring| ring.intersections.iter().cloned())
+            .collect();
+        let mut groups: Vec<IntersectionGroup> = graph
+            .intersections
+            .keys()
+            .filter(|i| !on_ring.contains(i))
+            .map(|i| IntersectionGroup {
+                intersections: vec![*i],
+                ways: HashSet::new(),
+            })
+            .collect();
+        groups.extend(rings);
+
         let mut junctions = Vec::new();
-        for (i, intersection) in &graph.intersections {
-            if self.derived_nodes[&intersection.osm_node]
-                .tags
-                .is("highway", "crossing")
+        for group in groups {
+            if group.ways.is_empty()
+                && self.derived_nodes[&graph.intersections[&group.intersections[0]].osm_node]
+                    .tags
+                    .is("highway", "crossing")
             {
                 continue;
             }
 
-            let mut any_severances = false;
+            let mut any_severances = group
+                .ways
+                .iter()
+                .any(|w| self.derived_ways[w].is_severance());
             let mut any_roads = false;
             let mut arms = Vec::new();
             let mut crossings = BTreeSet::new();
             let mut explicit_non_crossings = BTreeSet::new();
-            let mut number_roundabout_arms = 0;
             let mut number_motorway_arms = 0;
+            let mut coverage = Vec::new();
             let mut seen_edges = BTreeSet::new();
-            for e in &intersection.edges {
+            let mut arm_edges = Vec::new();
+            for i in &group.intersections {
+                for e in &graph.intersections[i].edges {
+                    // The roundabout itself isn't an arm
+                    if group.ways.contains(&graph.edges[e].osm_way) {
+                        continue;
+                    }
+                    arm_edges.push((*i, *e));
+                }
+            }
+            for (i, e) in &arm_edges {
                 let edge = &graph.edges[e];
                 let way = &self.derived_ways[&edge.osm_way];
                 if way.is_severance() {
@@ -188,27 +381,23 @@ impl Speedwalk {
 
                 // Look for the first crossing (or crossing=no) along this arm
                 let mut has_crossing = false;
+                let mut arm_coverage = ArmCoverage::Missing;
                 for n in nodes_reached {
                     let node = &self.derived_nodes[&n];
                     if node.is_explicit_crossing_no() {
                         explicit_non_crossings.insert(n);
                         has_crossing = true;
+                        arm_coverage = ArmCoverage::ExplicitNo(n);
                         break;
                     }
                     if node.is_crossing() {
                         crossings.insert(n);
                         has_crossing = true;
+                        arm_coverage = ArmCoverage::Crossing(n);
                         break;
                     }
                 }
 
-                if options.ignore_roundabouts
-                    && way.tags.is_any("junction", vec!["circular", "roundabout"])
-                {
-                    has_crossing = true;
-                    number_roundabout_arms += 1;
-                }
-
                 if options.ignore_motorways
                     && way.tags.is_any(
                         "highway",
@@ -217,23 +406,35 @@ impl Speedwalk {
                 {
                     has_crossing = true;
                     number_motorway_arms += 1;
+                    if arm_coverage == ArmCoverage::Missing {
+                        arm_coverage = ArmCoverage::Ignored;
+                    }
                 }
 
                 arms.push((*e, arm_ls, has_crossing));
+                coverage.push(arm_coverage);
             }
 
             let number_dual_carriageway_splits =
-                self.count_dual_carriageway_splits(graph, *i, &arms);
+                self.count_dual_carriageway_splits(graph, &group.intersections, &arms);
 
             if any_roads
                 && (any_severances || !options.only_major_roads)
                 && (arms.len() - number_dual_carriageway_splits) > 2
             {
+                // For roundabouts, use the middle of the ring
+                let n = group.intersections.len() as f64;
+                let (sum_x, sum_y) = group
+                    .intersections
+                    .iter()
+                    .map(|i| graph.intersections[i].point)
+                    .fold((0.0, 0.0), |(x, y), pt| (x + pt.x(), y + pt.y()));
                 junctions.push(Junction {
-                    i: *i,
+                    centroid: Point::new(sum_x / n, sum_y / n),
+                    intersections: group.intersections,
                     arms,
+                    coverage,
                     number_dual_carriageway_splits,
-                    number_roundabout_arms,
                     number_motorway_arms,
                     crossings,
                     explicit_non_crossings,
@@ -243,14 +444,61 @@ impl Speedwalk {
         junctions
     }
 
+    /// Find every roundabout, and all the intersections around it. Roundabouts split into
+    /// multiple ways are joined.
+    fn find_roundabouts(&self, graph: &Graph) -> Vec<IntersectionGroup> {
+        let mut ways_per_node: HashMap<NodeID, Vec<WayID>> = HashMap::new();
+        let mut remaining = BTreeSet::new();
+        for (id, way) in &self.derived_ways {
+            if way.kind.is_road() && way.tags.is_any("junction", vec!["circular", "roundabout"]) {
+                remaining.insert(*id);
+                for n in &way.node_ids {
+                    ways_per_node.entry(*n).or_default().push(*id);
+                }
+            }
+        }
+
+        let mut roundabouts = Vec::new();
+        while let Some(start) = remaining.pop_first() {
+            let mut ways = HashSet::from([start]);
+            let mut queue = vec![start];
+            while let Some(w) = queue.pop() {
+                for n in &self.derived_ways[&w].node_ids {
+                    for next in &ways_per_node[n] {
+                        if remaining.remove(next) {
+                            ways.insert(*next);
+                            queue.push(*next);
+                        }
+                    }
+                }
+            }
+
+            let intersections = graph
+                .intersections
+                .values()
+                .filter(|i| {
+                    i.edges
+                        .iter()
+                        .any(|e| ways.contains(&graph.edges[e].osm_way))
+                })
+                .map(|i| i.id)
+                .collect();
+            roundabouts.push(IntersectionGroup {
+                intersections,
+                ways,
+            });
+        }
+        roundabouts
+    }
+
     fn count_dual_carriageway_splits(
         &self,
         graph: &Graph,
-        i: IntersectionID,
+        intersections: &[IntersectionID],
         arms: &Vec<(EdgeID, LineString, bool)>,
     ) -> usize {
-        // For each one-way road, track its (name, whether it points at the intersection (true)
-        // or away from it (false), angle)
+        // For each one-way road, track its (name, whether it points at the junction (true) or
+        // away from it (false), angle)
         let mut oneway_roads: Vec<(String, bool, f64)> = Vec::new();
         let mut num_splits = 0;
         for (e, _, _) in arms {
@@ -260,7 +508,7 @@ impl Speedwalk {
                 && way.tags.is("oneway", "yes")
                 && let Some(name) = way.tags.get("name")
             {
-                let dir = edge.dst == i;
+                let dir = intersections.contains(&edge.dst);
                 let angle = angle_ls_directional(&edge.linestring);
 
                 if oneway_roads
@@ -330,21 +578,278 @@ impl Speedwalk {
     }
 }
 
-struct Junction {
-    i: IntersectionID,
+pub(crate) struct Junction {
+    /// The middle of the junction, or of the ring for roundabouts
+    pub centroid: Point,
     // The LineString is up to options.max_distance along the edge's way. The bool is true if
     // there's a crossing node on this arm.
-    arms: Vec<(EdgeID, LineString, bool)>,
+    pub arms: Vec<(EdgeID, LineString, bool)>,
+    /// What was found along each arm, in the same order as `arms`
+    coverage: Vec<ArmCoverage>,
+    /// Usually just one, but every intersection around a roundabout
+    intersections: Vec<IntersectionID>,
     number_dual_carriageway_splits: usize,
-    number_roundabout_arms: usize,
     number_motorway_arms: usize,
-    crossings: BTreeSet<NodeID>,
+    pub crossings: BTreeSet<NodeID>,
     explicit_non_crossings: BTreeSet<NodeID>,
 }
 
+impl Junction {
+    /// True if this is every intersection around a roundabout
+    pub fn is_roundabout(&self) -> bool {
+        self.intersections.len() > 1
+    }
+
+    /// The node closest to the middle of the junction. Usually the only intersection.
+    pub fn center_node(&self, graph: &Graph) -> NodeID {
+        let i = self
+            .intersections
+            .iter()
+            .min_by(|a, b| {
+                let dist = |i: &IntersectionID| {
+                    Euclidean.distance(self.centroid, graph.intersections[i].point)
+                };
+                dist(a).total_cmp(&dist(b))
+            })
+            .unwrap();
+        graph.intersections[i].osm_node
+    }
+
+    /// The node of every intersection in the junction
+    pub fn nodes(&self, graph: &Graph) -> Vec<NodeID> {
+        self.intersections
+            .iter()
+            .map(|i| graph.intersections[i].osm_node)
+            .collect()
+    }
+}
+
+/// Some intersections treated as one junction. Normally this is just one intersection and no
+/// ways.
+struct IntersectionGroup {
+    intersections: Vec<IntersectionID>,
+    ways: HashSet<WayID>,
+}
+
+#[derive(Default, Serialize)]
+struct ArmScore {
+    /// crossing, crossing=no, ignored, or missing
+    coverage: &'static str,
+    node: Option<i64>,
+    control: Option<&'static str>,
+    /// From the middle of the junction to the crossing
+    distance: Option<f64>,
+    connected: Option<bool>,
+    /// Not set for ignored arms
+    score: Option<f64>,
+}
+
+struct JunctionScore {
+    arms: Vec<ArmScore>,
+    /// From 0 to 1
+    score: f64,
+}
+
+#[derive(Default, Serialize)]
+struct Summary {
+    timestamp: Option<i64>,
+    junctions: usize,
+    fully_scored_junctions: usize,
+    mean_score: f64,
+    arms_with_crossing: usize,
+    arms_with_crossing_no: usize,
+    arms_missing: usize,
+    crossings_by_control: BTreeMap<&'static str, usize>,
+    connected_crossings: usize,
+}
+
+/// traffic_signals, uncontrolled, unmarked, or unknown
+pub(crate) fn crossing_control(tags: &Tags) -> &'static str {
+    if tags.is_any(
+        "crossing",
+        vec!["traffic_signals", "pelican", "toucan", "puffin", "pegasus"],
+    ) || tags.is("highway", "traffic_signals")
+        || tags.is("crossing:signals", "yes")
+    {
+        "traffic_signals"
+    } else if tags.is("crossing", "unmarked") || tags.is("crossing:markings", "no") {
+        "unmarked"
+    } else if tags.is_any("crossing", vec!["uncontrolled", "zebra", "marked"])
+        || tags.has("crossing:markings")
+    {
+        "uncontrolled"
+    } else {
+        "unknown"
+    }
+}
+
+fn round2(x: f64) -> f64 {
+    (x * 100.0).round() / 100.0
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ArmCoverage {
+    Crossing(NodeID),
+    ExplicitNo(NodeID),
+    /// A motorway arm, not expected to have a crossing
+    Ignored,
+    Missing,
+}
+
 // Angle in degrees from first to last point. Includes the "direction" of the line.
 fn angle_ls_directional(ls: &LineString) -> f64 {
     let pt1 = ls.coords().next().unwrap();
     let pt2 = ls.coords().last().unwrap();
     (pt2.y - pt1.y).atan2(pt2.x - pt1.x).to_degrees()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score_junction() {
+        // A four-way junction. North has a signalised crossing, east has an unmarked crossing far
+        // away, south has crossing=no, and west has nothing.
+        let osm = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <node id="1" lon="0.000000" lat="0.000000" version="1" />
+  <node id="2" lon="0.000000" lat="0.000090" version="1">
+    <tag k="highway" v="crossing"/>
+    <tag k="crossing" v="traffic_signals"/>
+  </node>
+  <node id="3" lon="0.000000" lat="0.000450" version="1" />
+  <node id="4" lon="0.000270" lat="0.000000" version="1">
+    <tag k="highway" v="crossing"/>
+    <tag k="crossing" v="unmarked"/>
+  </node>
+  <node id="5" lon="0.000450" lat="0.000000" version="1" />
+  <node id="6" lon="0.000000" lat="-0.000090" version="1">
+    <tag k="crossing" v="no"/>
+  </node>
+  <node id="7" lon="0.000000" lat="-0.000450" version="1" />
+  <node id="8" lon="-0.000450" lat="0.000000" version="1" />
+  <way id="100" version="1">
+    <nd ref="1"/><nd ref="2"/><nd ref="3"/>
+    <tag k="highway" v="residential"/>
+    <tag k="sidewalk" v="both"/>
+  </way>
+  <way id="101" version="1">
+    <nd ref="1"/><nd ref="4"/><nd ref="5"/>
+    <tag k="highway" v="residential"/>
+    <tag k="sidewalk" v="both"/>
+  </way>
+  <way id="102" version="1">
+    <nd ref="1"/><nd ref="6"/><nd ref="7"/>
+    <tag k="highway" v="residential"/>
+    <tag k="sidewalk" v="both"/>
+  </way>
+  <way id="103" version="1">
+    <nd ref="1"/><nd ref="8"/>
+    <tag k="highway" v="residential"/>
+    <tag k="sidewalk" v="both"/>
+  </way>
+</osm>"#;
+        let model = Speedwalk::new_from_osm(osm.as_bytes(), None).unwrap();
+        let options = Options {
+            only_major_roads: false,
+            ignore_utility_roads: true,
+            ignore_cycleways: true,
+            ignore_footways: true,
+            ignore_roundabouts: true,
+            ignore_motorways: true,
+            max_distance: 40.0,
+        };
+
+        let graph = Graph::new(&model);
+        let junctions = model.find_junctions(&options, &graph);
+        assert_eq!(junctions.len(), 1);
+        let score = model.score_junction(&junctions[0]);
+        let mut arm_scores: Vec<_> = score.arms.iter().map(|arm| arm.score.unwrap()).collect();
+        arm_scores.sort_by(f64::total_cmp);
+        assert_eq!(arm_scores, vec![0.0, 0.48, 1.0, 1.0]);
+        assert!((score.score - 0.62).abs() < 1e-9);
+
+        let summary: serde_json::Value =
+            serde_json::from_str(&model.audit_crossings_summary(options).unwrap()).unwrap();
+        assert_eq!(summary["junctions"], 1);
+        assert_eq!(summary["arms_missing"], 1);
+        assert_eq!(summary["arms_with_crossing_no"], 1);
+        assert_eq!(summary["connected_crossings"], 2);
+        assert_eq!(summary["crossings_by_control"]["traffic_signals"], 1);
+        assert_eq!(summary["crossings_by_control"]["unmarked"], 1);
+    }
+
+    #[test]
+    fn test_roundabout_is_one_junction() {
+        // Four roads meet a small roundabout. North and west have crossings close to the
+        // roundabout, east only has one farther away, and south has none.
+        let osm = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <node id="11" lon="0.000090" lat="0.000000" version="1" />
+  <node id="12" lon="0.000000" lat="0.000090" version="1" />
+  <node id="13" lon="-0.000090" lat="0.000000" version="1" />
+  <node id="14" lon="0.000000" lat="-0.000090" version="1" />
+  <node id="21" lon="0.000000" lat="0.000180" version="1">
+    <tag k="highway" v="crossing"/>
+  </node>
+  <node id="22" lon="0.000000" lat="0.000600" version="1" />
+  <node id="31" lon="0.000540" lat="0.000000" version="1">
+    <tag k="highway" v="crossing"/>
+  </node>
+  <node id="32" lon="0.000800" lat="0.000000" version="1" />
+  <node id="41" lon="-0.000180" lat="0.000000" version="1">
+    <tag k="highway" v="crossing"/>
+  </node>
+  <node id="42" lon="-0.000600" lat="0.000000" version="1" />
+  <node id="51" lon="0.000000" lat="-0.000600" version="1" />
+  <way id="100" version="1">
+    <nd ref="11"/><nd ref="12"/><nd ref="13"/><nd ref="14"/><nd ref="11"/>
+    <tag k="highway" v="secondary"/>
+    <tag k="junction" v="roundabout"/>
+  </way>
+  <way id="101" version="1">
+    <nd ref="12"/><nd ref="21"/><nd ref="22"/>
+    <tag k="highway" v="residential"/>
+  </way>
+  <way id="102" version="1">
+    <nd ref="11"/><nd ref="31"/><nd ref="32"/>
+    <tag k="highway" v="residential"/>
+  </way>
+  <way id="103" version="1">
+    <nd ref="13"/><nd ref="41"/><nd ref="42"/>
+    <tag k="highway" v="residential"/>
+  </way>
+  <way id="104" version="1">
+    <nd ref="14"/><nd ref="51"/>
+    <tag k="highway" v="residential"/>
+  </way>
+</osm>"#;
+        let model = Speedwalk::new_from_osm(osm.as_bytes(), None).unwrap();
+        let options = Options {
+            only_major_roads: false,
+            ignore_utility_roads: true,
+            ignore_cycleways: true,
+            ignore_footways: true,
+            ignore_roundabouts: true,
+            ignore_motorways: true,
+            max_distance: 40.0,
+        };
+
+        let graph = Graph::new(&model);
+        let junctions = model.find_junctions(&options, &graph);
+        assert_eq!(junctions.len(), 1);
+        assert_eq!(junctions[0].intersections.len(), 4);
+        assert_eq!(junctions[0].arms.len(), 4);
+        let crossings: Vec<i64> = junctions[0].crossings.iter().map(|n| n.0).collect();
+        assert_eq!(crossings, vec![21, 41]);
+        assert_eq!(
+            junctions[0]
+                .coverage
+                .iter()
+                .filter(|c| **c == ArmCoverage::Missing)
+                .count(),
+            2
+        );
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Result, bail};
+use geo::{Geometry, Intersects, LineString, MultiPolygon, Point};
+use geojson::{Feature, FeatureCollection};
+use serde::Deserialize;
+
+use crate::{Problem, Speedwalk};
+
+#[derive(Default, Deserialize)]
+pub struct ChallengeOptions {
+    /// Make a separate challenge for each problem code
+    #[serde(default)]
+    pub group_by_code: bool,
+    /// Make a separate challenge for each of these polygons or multipolygons, using their `name`
+    /// property. Problems outside all of them are skipped.
+    #[serde(default)]
+    pub boundaries: Option<FeatureCollection>,
+}
+
+impl Speedwalk {
+    /// Turn every problem, except suppressed ones, into a MapRoulette task. Returns line-delimited
+    /// GeoJSON for each challenge, keyed by problem code and/or boundary name, or just "all".
+    pub fn export_challenges(&self, options: ChallengeOptions) -> Result<BTreeMap<String, String>> {
+        let mut boundaries: Vec<(String, MultiPolygon)> = Vec::new();
+        if let Some(fc) = options.boundaries {
+            for (idx, f) in fc.features.into_iter().enumerate() {
+                let name = f
+                    .property("name")
+                    .and_then(|x| x.as_str())
+                    .map(|x| x.to_string())
+                    .unwrap_or_else(|| format!("boundary {}", idx + 1));
+                let polygons = match f.try_into()? {
+                    Geometry::Polygon(polygon) => MultiPolygon::new(vec![polygon]),
+                    Geometry::MultiPolygon(polygons) => polygons,
+                    _ => bail!("{name} isn't a polygon or multipolygon"),
+                };
+                boundaries.push((name, polygons));
+            }
+        }
+
+        let mut elements: Vec<(String, Geometry, &Vec<Problem>)> = Vec::new();
+        let mut nodes: Vec<_> = self.derived_nodes.iter().collect();
+        nodes.sort_by_key(|(id, _)| **id);
+        for (id, node) in nodes {
+            let pt: Point = self.mercator.pt_to_wgs84(node.pt).into();
+            elements.push((format!("node/{}", id.0), pt.into(), &node.problems));
+        }
+        let mut ways: Vec<_> = self.derived_ways.iter().collect();
+        ways.sort_by_key(|(id, _)| **id);
+        for (id, way) in ways {
+            let ls: LineString = self.mercator.to_wgs84(&way.linestring);
+            elements.push((format!("way/{}", id.0), ls.into(), &way.problems));
+        }
+
+        let mut challenges: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (osm_id, geometry, problems) in elements {
+            if problems.is_empty() {
+                continue;
+            }
+            let boundary = if boundaries.is_empty() {
+                None
+            } else {
+                let Some((name, _)) = boundaries
+                    .iter()
+                    .find(|(_, polygons)| polygons.intersects(&geometry))
+                else {
+                    continue;
+                };
+                Some(name.as_str())
+            };
+
+            for (idx, problem) in problems.iter().enumerate() {
+                if problem.suppressed {
+                    continue;
+                }
+                let code = problem.problem_type.code();
+                // One element can have a few problems of the same type, so count them for a unique
+                // task ID
+                let nth = problems[..idx]
+                    .iter()
+                    .filter(|p| p.problem_type == problem.problem_type)
+                    .count();
+                let mut f = Feature::from(geojson::Geometry::from(&geometry));
+                f.id = Some(geojson::feature::Id::String(format!(
+                    "{osm_id}/{code}/{nth}"
+                )));
+                f.set_property("@id", osm_id.clone());
+                f.set_property("code", code);
+                f.set_property(
+                    "severity",
+                    serde_json::to_value(problem.problem_type.severity())?,
+                );
+                f.set_property(
+                    "category",
+                    serde_json::to_value(problem.problem_type.category())?,
+                );
+                f.set_property(
+                    "instruction",
+                    format!("{}. {}.", problem.note, problem.problem_type.fix()),
+                );
+
+                let mut features = vec![f];
+                features.extend(problem.details.iter().cloned());
+                let task = FeatureCollection {
+                    bbox: None,
+                    features,
+                    foreign_members: None,
+                };
+
+                let key = match (options.group_by_code, boundary) {
+                    (true, Some(name)) => format!("{code}/{name}"),
+                    (true, None) => code.to_string(),
+                    (false, Some(name)) => name.to_string(),
+                    (false, None) => "all".to_string(),
+                };
+                challenges
+                    .entry(key)
+                    .or_default()
+                    .push(serde_json::to_string(&task)?);
+            }
+        }
+
+        Ok(challenges
+            .into_iter()
+            .map(|(key, lines)| (key, lines.join("\n") + "\n"))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ProblemType;
+    use osm_reader::WayID;
+
+    #[test]
+    fn test_export_challenges() {
+        // Only way 100 has problems, from the ambiguous sidewalk=separate
+        let osm = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <node id="1" lon="0.000000" lat="0.000000" version="1" />
+  <node id="2" lon="0.000900" lat="0.000000" version="1" />
+  <node id="3" lon="0.001800" lat="0.000000" version="1" />
+  <way id="100" version="1">
+    <nd ref="1"/><nd ref="2"/>
+    <tag k="highway" v="residential"/>
+    <tag k="sidewalk" v="separate"/>
+  </way>
+  <way id="101" version="1">
+    <nd ref="2"/><nd ref="3"/>
+    <tag k="highway" v="residential"/>
+    <tag k="sidewalk" v="no"/>
+  </way>
+</osm>"#;
+        let model = Speedwalk::new_from_osm(osm.as_bytes(), None).unwrap();
+
+        let all = model
+            .export_challenges(ChallengeOptions::default())
+            .unwrap();
+        assert_eq!(all.keys().collect::<Vec<_>>(), vec!["all"]);
+        let tasks: Vec<FeatureCollection> = all["all"]
+            .lines()
+            .map(|line| {
+                line.parse::<geojson::GeoJson>()
+                    .unwrap()
+                    .try_into()
+                    .unwrap()
+            })
+            .collect();
+        assert!(!tasks.is_empty());
+        for task in &tasks {
+            let f = &task.features[0];
+            assert_eq!(f.property("@id").unwrap(), "way/100");
+            assert!(f.property("instruction").unwrap().is_string());
+        }
+
+        let by_code = model
+            .export_challenges(ChallengeOptions {
+                group_by_code: true,
+                boundaries: None,
+            })
+            .unwrap();
+        assert!(by_code.contains_key("ambiguous_sidewalk_separate"));
+        assert_eq!(
+            by_code.values().map(|x| x.lines().count()).sum::<usize>(),
+            tasks.len()
+        );
+    }
+
+    #[test]
+    fn test_multipolygon_boundary() {
+        let osm = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <node id="1" lon="0.000000" lat="0.000000" version="1" />
+  <node id="2" lon="0.000900" lat="0.000000" version="1" />
+  <way id="100" version="1">
+    <nd ref="1"/><nd ref="2"/>
+    <tag k="highway" v="residential"/>
+    <tag k="sidewalk" v="separate"/>
+  </way>
+</osm>"#;
+        let model = Speedwalk::new_from_osm(osm.as_bytes(), None).unwrap();
+        // The second part covers the way
+        let boundaries: FeatureCollection = r#"{
+  "type": "FeatureCollection",
+  "features": [{
+    "type": "Feature",
+    "properties": {"name": "district"},
+    "geometry": {
+      "type": "MultiPolygon",
+      "coordinates": [
+        [[[1.0, 1.0], [1.1, 1.0], [1.1, 1.1], [1.0, 1.0]]],
+        [[[-0.001, -0.001], [0.001, -0.001], [0.001, 0.001], [-0.001, 0.001], [-0.001, -0.001]]]
+      ]
+    }
+  }]
+}"#
+        .parse()
+        .unwrap();
+
+        let challenges = model
+            .export_challenges(ChallengeOptions {
+                group_by_code: false,
+                boundaries: Some(boundaries),
+            })
+            .unwrap();
+        assert_eq!(challenges.keys().collect::<Vec<_>>(), vec!["district"]);
+    }
+
+    #[test]
+    fn test_unique_task_ids() {
+        let osm = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <node id="1" lon="0.000000" lat="0.000000" version="1" />
+  <node id="2" lon="0.000900" lat="0.000000" version="1" />
+  <way id="100" version="1">
+    <nd ref="1"/><nd ref="2"/>
+    <tag k="highway" v="residential"/>
+    <tag k="sidewalk" v="no"/>
+  </way>
+</osm>"#;
+        let mut model = Speedwalk::new_from_osm(osm.as_bytes(), None).unwrap();
+        let way = model.derived_ways.get_mut(&WayID(100)).unwrap();
+        assert!(way.problems.is_empty());
+        for _ in 0..2 {
+            way.problems.push(Problem::new(
+                ProblemType::FootwayCrossesRoadWithoutNode,
+                Vec::new(),
+            ));
+        }
+
+        let all = model
+            .export_challenges(ChallengeOptions::default())
+            .unwrap();
+        let ids: Vec<String> = all["all"]
+            .lines()
+            .map(|line| {
+                let task: FeatureCollection = line
+                    .parse::<geojson::GeoJson>()
+                    .unwrap()
+                    .try_into()
+                    .unwrap();
+                match task.features[0].id.clone().unwrap() {
+                    geojson::feature::Id::String(id) => id,
+                    geojson::feature::Id::Number(_) => unreachable!(),
+                }
+            })
+            .collect();
+        assert_eq!(
+            ids,
+            vec![
+                "way/100/footway_crosses_road_without_node/0",
+                "way/100/footway_crosses_road_without_node/1",
+            ]
+        );
+    }
+}
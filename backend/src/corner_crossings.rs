@@ -0,0 +1,513 @@
+use std::collections::HashMap;
+
+use geo::{
+    Coord, Distance, Euclidean, InterpolatableLine, Intersects, Length, Line, LineString, Point,
+};
+use osm_reader::WayID;
+use rstar::{RTree, primitives::GeomWithData};
+use utils::{Tags, aabb, buffer_aabb};
+
+use crate::{
+    Kind, Provenance, Speedwalk,
+    audit::{Junction, Options},
+    crossings::{angle_of_line, angle_of_pt_on_line, first_sidewalk_hit},
+    edits::CreateNewGeometry,
+    graph::Graph,
+};
+
+/// How far to look to each side of a junction arm for a sidewalk
+const CORNER_PROBE_DISTANCE: f64 = 15.0;
+/// How far apart to try places along each junction arm
+const CORNER_STEP: f64 = 1.0;
+/// How far to look from the middle of a junction for the sidewalk at each corner
+const SCRAMBLE_PROBE_DISTANCE: f64 = 25.0;
+/// Between two junction arms at least this far apart, like on the straight side of a T-junction,
+/// there are two corners to cross diagonally from
+const WIDE_CORNER_DEGREES: f64 = 150.0;
+/// An existing crossing way this close to the middle of a junction is probably a diagonal one
+const DIAGONAL_CROSSING_DISTANCE: f64 = 3.0;
+
+impl Speedwalk {
+    /// For every junction arm without a crossing, create a crossing way between the sidewalks on
+    /// either side of it, as close to the junction as possible. A crossing node tagged with
+    /// `crossing_value` is inserted where it crosses the road. At pedestrian scrambles, also
+    /// create diagonal crossings between corners that aren't next to each other.
+    pub fn connect_junction_corners(
+        &self,
+        options: &Options,
+        crossing_value: &str,
+    ) -> CreateNewGeometry {
+        let graph = Graph::new(self);
+        let lookups = Lookups::new(self);
+
+        let mut node_tags = Tags::empty();
+        node_tags.insert("highway", "crossing");
+        node_tags.insert("crossing", crossing_value);
+        let mut way_tags = Tags::empty();
+        way_tags.insert("highway", "footway");
+        way_tags.insert("footway", "crossing");
+        way_tags.insert("crossing", crossing_value);
+        let mut scramble_tags = Tags::empty();
+        scramble_tags.insert("highway", "footway");
+        scramble_tags.insert("footway", "crossing");
+        scramble_tags.insert("crossing", "traffic_signals");
+        scramble_tags.insert("crossing:scramble", "yes");
+
+        let mut new_crossings = Vec::new();
+        let mut insert_new_nodes = HashMap::new();
+        for junction in self.find_junctions(options, &graph) {
+            for (e, arm, has_crossing) in &junction.arms {
+                let road = graph.edges[e].osm_way;
+                if *has_crossing || !self.derived_ways[&road].kind.is_road() {
+                    continue;
+                }
+                let Some((road_pt, [(sidewalk1, endpt1), (sidewalk2, endpt2)])) =
+                    self.find_corners(arm, road, &lookups)
+                else {
+                    continue;
+                };
+
+                new_crossings.push((
+                    LineString::new(vec![endpt1, road_pt, endpt2]),
+                    way_tags.clone(),
+                    Provenance {
+                        osm_node: Some(junction.center_node(&graph)),
+                        osm_way: Some(road),
+                        side: None,
+                    },
+                ));
+                for (way, pt, tags) in [
+                    (road, road_pt, node_tags.clone()),
+                    (sidewalk1, endpt1, Tags::empty()),
+                    (sidewalk2, endpt2, Tags::empty()),
+                ] {
+                    insert_new_nodes
+                        .entry(way)
+                        .or_insert_with(Vec::new)
+                        .push((pt, tags));
+                }
+            }
+
+            // The diagonals meet the roads at one shared node. The middle of a roundabout isn't on
+            // any road, so don't try there.
+            if junction.is_roundabout() || !self.is_scramble(&junction, &graph, &lookups) {
+                continue;
+            }
+            let junction_node = junction.center_node(&graph);
+            let center = self.derived_nodes[&junction_node].pt;
+            for [(sidewalk1, endpt1), (sidewalk2, endpt2)] in
+                self.scramble_diagonals(&junction, &graph, &lookups)
+            {
+                new_crossings.push((
+                    LineString::new(vec![endpt1, center, endpt2]),
+                    scramble_tags.clone(),
+                    Provenance {
+                        osm_node: Some(junction_node),
+                        osm_way: None,
+                        side: None,
+                    },
+                ));
+                for (way, pt) in [(sidewalk1, endpt1), (sidewalk2, endpt2)] {
+                    insert_new_nodes
+                        .entry(way)
+                        .or_insert_with(Vec::new)
+                        .push((pt, Tags::empty()));
+                }
+            }
+        }
+        info!("Made {} crossings at junction corners", new_crossings.len());
+
+        CreateNewGeometry {
+            new_ways: new_crossings,
+            new_kind: Kind::Crossing,
+            insert_new_nodes,
+            modify_existing_way_tags: HashMap::new(),
+        }
+    }
+
+    /// Walks along a junction arm, starting from the junction, until a line perpendicular to the
+    /// road reaches sidewalks on both sides without crossing anything. Returns the point on the
+    /// road, and the sidewalk and point on each side.
+    fn find_corners(
+        &self,
+        arm: &LineString,
+        road: WayID,
+        lookups: &Lookups,
+    ) -> Option<(Coord, [(WayID, Coord); 2])> {
+        let length = Euclidean.length(arm);
+        let mut dist = CORNER_STEP;
+        while dist < length {
+            let road_pt: Coord = arm.point_at_distance_from_start(&Euclidean, dist)?.into();
+            let angle = angle_of_pt_on_line(arm, road_pt);
+            if let (Some(hit1), Some(hit2)) = (
+                self.clear_sidewalk_hit(
+                    road_pt,
+                    angle + 90.0,
+                    CORNER_PROBE_DISTANCE,
+                    &[road],
+                    lookups,
+                ),
+                self.clear_sidewalk_hit(
+                    road_pt,
+                    angle - 90.0,
+                    CORNER_PROBE_DISTANCE,
+                    &[road],
+                    lookups,
+                ),
+            ) && Euclidean.distance(hit1.1, hit2.1) >= 1.0
+            {
+                return Some((road_pt, [hit1, hit2]));
+            }
+            dist += CORNER_STEP;
+        }
+        None
+    }
+
+    /// A junction is a pedestrian scramble if it's tagged as one, or if it has traffic signals
+    /// and some diagonal crossings are already mapped.
+    fn is_scramble(&self, junction: &Junction, graph: &Graph, lookups: &Lookups) -> bool {
+        let junction_nodes = junction.nodes(graph);
+        let nodes: Vec<_> = junction
+            .crossings
+            .iter()
+            .chain(&junction_nodes)
+            .map(|n| &self.derived_nodes[n])
+            .collect();
+        if nodes
+            .iter()
+            .any(|node| node.tags.is("crossing:scramble", "yes"))
+        {
+            return true;
+        }
+        nodes.iter().any(|node| {
+            node.tags.is("highway", "traffic_signals")
+                || node.tags.is("crossing", "traffic_signals")
+        }) && lookups.has_crossing_near(&[junction.centroid.into()])
+    }
+
+    /// Finds the sidewalk at each corner of a junction, then returns pairs of corners that
+    /// aren't next to each other and don't already have a crossing between them.
+    fn scramble_diagonals(
+        &self,
+        junction: &Junction,
+        graph: &Graph,
+        lookups: &Lookups,
+    ) -> Vec<[(WayID, Coord); 2]> {
+        let center = junction.centroid.into();
+        // Probes may cross any road meeting at the junction
+        let junction_roads: Vec<WayID> = junction
+            .nodes(graph)
+            .into_iter()
+            .flat_map(|n| self.derived_nodes[&n].way_ids.clone())
+            .collect();
+
+        // Each arm starts at the junction
+        let mut arm_angles: Vec<f64> = junction
+            .arms
+            .iter()
+            .filter_map(|(_, arm, _)| {
+                let pt =
+                    arm.point_at_distance_from_start(&Euclidean, Euclidean.length(arm).min(5.0))?;
+                Some(angle_of_line(Line::new(center, pt.into())).rem_euclid(360.0))
+            })
+            .collect();
+        arm_angles.sort_by(f64::total_cmp);
+
+        // Look for corners between consecutive arms, in order around the junction
+        let mut corner_angles = Vec::new();
+        for (idx, angle) in arm_angles.iter().enumerate() {
+            let next = arm_angles
+                .get(idx + 1)
+                .copied()
+                .unwrap_or(arm_angles[0] + 360.0);
+            if next - angle >= WIDE_CORNER_DEGREES {
+                corner_angles.push(angle + 45.0);
+                corner_angles.push(next - 45.0);
+            } else {
+                corner_angles.push((angle + next) / 2.0);
+            }
+        }
+        let corners: Vec<Option<(WayID, Coord)>> = corner_angles
+            .into_iter()
+            .map(|angle| {
+                self.clear_sidewalk_hit(
+                    center,
+                    angle,
+                    SCRAMBLE_PROBE_DISTANCE,
+                    &junction_roads,
+                    lookups,
+                )
+            })
+            .collect();
+
+        let n = corners.len();
+        let mut diagonals = Vec::new();
+        for i in 0..n {
+            for j in (i + 2)..n {
+                // The first and last corners are next to each other
+                if i == 0 && j == n - 1 {
+                    continue;
+                }
+                if let (Some(corner1), Some(corner2)) = (corners[i], corners[j])
+                    && !lookups.has_crossing_near(&[corner1.1, center, corner2.1])
+                {
+                    diagonals.push([corner1, corner2]);
+                }
+            }
+        }
+        diagonals
+    }
+
+    /// Finds the first sidewalk in some direction, as long as no other road or building is in the
+    /// way.
+    fn clear_sidewalk_hit(
+        &self,
+        pt: Coord,
+        angle: f64,
+        distance: f64,
+        ignore_roads: &[WayID],
+        lookups: &Lookups,
+    ) -> Option<(WayID, Coord)> {
+        let hit = first_sidewalk_hit(&lookups.closest_sidewalk, pt, angle, distance)?;
+        let test_line = LineString::new(vec![pt, hit.1]);
+        if lookups
+            .closest_road
+            .locate_in_envelope_intersecting(&aabb(&test_line))
+            .any(|obj| !ignore_roads.contains(&obj.data) && obj.geom().intersects(&test_line))
+        {
+            return None;
+        }
+        if self
+            .closest_building
+            .locate_in_envelope_intersecting(&aabb(&test_line))
+            .any(|polygon| polygon.intersects(&test_line))
+        {
+            return None;
+        }
+        Some(hit)
+    }
+}
+
+struct Lookups {
+    closest_sidewalk: RTree<GeomWithData<LineString, WayID>>,
+    closest_road: RTree<GeomWithData<LineString, WayID>>,
+    closest_crossing: RTree<GeomWithData<LineString, WayID>>,
+}
+
+impl Lookups {
+    fn new(model: &Speedwalk) -> Self {
+        let rtree = |filter: fn(&crate::Way) -> bool| {
+            RTree::bulk_load(
+                model
+                    .derived_ways
+                    .iter()
+                    .filter(|(_, way)| filter(way))
+                    .map(|(id, way)| GeomWithData::new(way.linestring.clone(), *id))
+                    .collect(),
+            )
+        };
+        Self {
+            closest_sidewalk: rtree(|way| way.kind == Kind::Sidewalk || way.is_walkable_other()),
+            closest_road: rtree(|way| way.kind.is_road()),
+            closest_crossing: rtree(|way| way.kind == Kind::Crossing),
+        }
+    }
+
+    /// Is there one crossing way close to all of these points?
+    fn has_crossing_near(&self, pts: &[Coord]) -> bool {
+        let bbox = buffer_aabb(
+            aabb(&LineString::new(pts.to_vec())),
+            DIAGONAL_CROSSING_DISTANCE,
+        );
+        self.closest_crossing
+            .locate_in_envelope_intersecting(&bbox)
+            .any(|obj| {
+                pts.iter().all(|pt| {
+                    Euclidean.distance(obj.geom(), &Point::from(*pt)) < DIAGONAL_CROSSING_DISTANCE
+                })
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{UserCmd, make_sidewalks::SidewalkOptions};
+
+    /// Generates sidewalks for all the roads, then connects junction corners
+    fn connect_corners(osm: &str) -> (Speedwalk, CreateNewGeometry) {
+        let mut model = Speedwalk::new_from_osm(osm.as_bytes(), None).unwrap();
+        let mut edits = model.take_edits();
+        edits
+            .apply_cmd(
+                UserCmd::MakeAllSidewalks(false, SidewalkOptions::default()),
+                &model,
+            )
+            .unwrap();
+        model.set_edits(edits);
+        model.after_edit();
+
+        let options: Options = serde_json::from_value(serde_json::json!({
+            "only_major_roads": false,
+            "ignore_utility_roads": false,
+            "ignore_cycleways": false,
+            "ignore_footways": false,
+            "ignore_roundabouts": true,
+            "ignore_motorways": false,
+            "max_distance": 30.0,
+        }))
+        .unwrap();
+        let results = model.connect_junction_corners(&options, "unknown");
+        (model, results)
+    }
+
+    /// A four-way junction. The junction node has extra tags.
+    fn four_way(junction_tags: &str) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <node id="1" lon="0.000000" lat="0.000000" version="1">{junction_tags}</node>
+  <node id="2" lon="-0.000450" lat="0.000000" version="1" />
+  <node id="3" lon="0.000450" lat="0.000000" version="1" />
+  <node id="4" lon="0.000000" lat="-0.000450" version="1" />
+  <node id="5" lon="0.000000" lat="0.000450" version="1" />
+  <way id="100" version="1">
+    <nd ref="2"/><nd ref="1"/><nd ref="3"/>
+    <tag k="highway" v="residential"/>
+    <tag k="sidewalk" v="both"/>
+  </way>
+  <way id="101" version="1">
+    <nd ref="4"/><nd ref="1"/><nd ref="5"/>
+    <tag k="highway" v="residential"/>
+    <tag k="sidewalk" v="both"/>
+  </way>
+</osm>"#
+        )
+    }
+
+    fn count_diagonals(results: &CreateNewGeometry) -> usize {
+        results
+            .new_ways
+            .iter()
+            .filter(|(_, tags, _)| tags.is("crossing:scramble", "yes"))
+            .count()
+    }
+
+    #[test]
+    fn test_connect_junction_corners() {
+        let (model, results) = connect_corners(&four_way(""));
+        assert_eq!(results.new_ways.len(), 4);
+        assert_eq!(count_diagonals(&results), 0);
+        let junction = Point::from(model.derived_nodes[&osm_reader::NodeID(1)].pt);
+        for (ls, tags, _) in &results.new_ways {
+            assert!(tags.is("crossing", "unknown"));
+            // Each crossing is close to the junction, just past the sidewalk corners
+            let road_pt = Point::from(ls.0[1]);
+            assert!(Euclidean.distance(road_pt, junction) < 10.0);
+        }
+    }
+
+    #[test]
+    fn test_scramble_four_arms() {
+        let (_, results) = connect_corners(&four_way(
+            r#"<tag k="highway" v="traffic_signals"/><tag k="crossing:scramble" v="yes"/>"#,
+        ));
+        assert_eq!(count_diagonals(&results), 2);
+        assert_eq!(results.new_ways.len(), 6);
+    }
+
+    #[test]
+    fn test_no_scramble_at_roundabout() {
+        // A signalised roundabout tagged as a scramble. Its middle isn't on any road, so no
+        // diagonals should be made there.
+        let osm = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <node id="11" lon="0.000090" lat="0.000000" version="1">
+    <tag k="highway" v="traffic_signals"/>
+    <tag k="crossing:scramble" v="yes"/>
+  </node>
+  <node id="12" lon="0.000000" lat="0.000090" version="1" />
+  <node id="13" lon="-0.000090" lat="0.000000" version="1" />
+  <node id="14" lon="0.000000" lat="-0.000090" version="1" />
+  <node id="22" lon="0.000000" lat="0.000600" version="1" />
+  <node id="32" lon="0.000800" lat="0.000000" version="1" />
+  <node id="42" lon="-0.000600" lat="0.000000" version="1" />
+  <node id="52" lon="0.000000" lat="-0.000600" version="1" />
+  <way id="100" version="1">
+    <nd ref="11"/><nd ref="12"/><nd ref="13"/><nd ref="14"/><nd ref="11"/>
+    <tag k="highway" v="secondary"/>
+    <tag k="junction" v="roundabout"/>
+  </way>
+  <way id="101" version="1">
+    <nd ref="12"/><nd ref="22"/>
+    <tag k="highway" v="residential"/>
+    <tag k="sidewalk" v="both"/>
+  </way>
+  <way id="102" version="1">
+    <nd ref="11"/><nd ref="32"/>
+    <tag k="highway" v="residential"/>
+    <tag k="sidewalk" v="both"/>
+  </way>
+  <way id="103" version="1">
+    <nd ref="13"/><nd ref="42"/>
+    <tag k="highway" v="residential"/>
+    <tag k="sidewalk" v="both"/>
+  </way>
+  <way id="104" version="1">
+    <nd ref="14"/><nd ref="52"/>
+    <tag k="highway" v="residential"/>
+    <tag k="sidewalk" v="both"/>
+  </way>
+</osm>"#;
+        let (_, results) = connect_corners(osm);
+        assert_eq!(count_diagonals(&results), 0);
+    }
+
+    #[test]
+    fn test_scramble_three_arms() {
+        // A T-junction. From each corner of the side road, there's a diagonal to the far side of
+        // the main road.
+        let osm = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <node id="1" lon="0.000000" lat="0.000000" version="1">
+    <tag k="highway" v="traffic_signals"/>
+    <tag k="crossing:scramble" v="yes"/>
+  </node>
+  <node id="2" lon="-0.000450" lat="0.000000" version="1" />
+  <node id="3" lon="0.000450" lat="0.000000" version="1" />
+  <node id="4" lon="0.000000" lat="-0.000450" version="1" />
+  <way id="100" version="1">
+    <nd ref="2"/><nd ref="1"/><nd ref="3"/>
+    <tag k="highway" v="residential"/>
+    <tag k="sidewalk" v="both"/>
+  </way>
+  <way id="101" version="1">
+    <nd ref="4"/><nd ref="1"/>
+    <tag k="highway" v="residential"/>
+    <tag k="sidewalk" v="both"/>
+  </way>
+</osm>"#;
+        let (model, results) = connect_corners(osm);
+        assert_eq!(count_diagonals(&results), 2);
+        assert_eq!(results.new_ways.len(), 5);
+
+        // Both diagonals end on the sidewalk along the north side of the main road, on either side
+        // of the junction
+        let junction = model.derived_nodes[&osm_reader::NodeID(1)].pt;
+        let mut north_ends: Vec<f64> = results
+            .new_ways
+            .iter()
+            .filter(|(_, tags, _)| tags.is("crossing:scramble", "yes"))
+            .map(|(ls, _, _)| {
+                // Mercator y grows southwards
+                if ls.0[0].y < junction.y {
+                    ls.0[0].x
+                } else {
+                    ls.0[2].x
+                }
+            })
+            .collect();
+        north_ends.sort_by(f64::total_cmp);
+        assert!(north_ends[0] < junction.x && north_ends[1] > junction.x);
+    }
+}
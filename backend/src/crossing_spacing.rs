@@ -0,0 +1,222 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use anyhow::Result;
+use geo::{Distance, Euclidean, LineString, Point};
+use geojson::GeoJson;
+use osm_reader::{NodeID, WayID};
+use serde::Deserialize;
+
+use crate::Speedwalk;
+
+#[derive(Deserialize)]
+pub struct SpacingOptions {
+    /// Report stretches of severance roads longer than this without a crossing
+    #[serde(default = "default_max_spacing")]
+    pub max_spacing: f64,
+}
+
+impl Default for SpacingOptions {
+    fn default() -> Self {
+        Self {
+            max_spacing: default_max_spacing(),
+        }
+    }
+}
+
+fn default_max_spacing() -> f64 {
+    200.0
+}
+
+/// A continuous road, made of ways with the same name joined end to end
+struct Corridor {
+    name: Option<String>,
+    ways: Vec<WayID>,
+    nodes: Vec<NodeID>,
+}
+
+impl Speedwalk {
+    /// Find long stretches of severance roads without any crossing node
+    pub fn audit_crossing_spacing(&self, options: SpacingOptions) -> Result<String> {
+        let mut features = Vec::new();
+
+        for corridor in self.find_corridors() {
+            let pts: Vec<Point> = corridor
+                .nodes
+                .iter()
+                .map(|n| Point::from(self.derived_nodes[n].pt))
+                .collect();
+            let mut dist_along = vec![0.0];
+            for pair in pts.windows(2) {
+                dist_along.push(dist_along.last().unwrap() + Euclidean.distance(pair[0], pair[1]));
+            }
+
+            let is_crossing = |idx: usize| self.derived_nodes[&corridor.nodes[idx]].is_crossing();
+            let mut bounds = vec![0];
+            bounds.extend((0..corridor.nodes.len()).filter(|idx| is_crossing(*idx)));
+            bounds.push(corridor.nodes.len() - 1);
+
+            for pair in bounds.windows(2) {
+                let (start, end) = (pair[0], pair[1]);
+                let length = dist_along[end] - dist_along[start];
+                if length <= options.max_spacing {
+                    continue;
+                }
+
+                let mut f = self
+                    .mercator
+                    .to_wgs84_gj(&LineString::from(pts[start..=end].to_vec()));
+                f.set_property("length", (length * 100.0).round() / 100.0);
+                f.set_property("name", corridor.name.clone());
+                f.set_property(
+                    "ways",
+                    corridor.ways.iter().map(|w| w.0).collect::<Vec<_>>(),
+                );
+                f.set_property(
+                    "endpoints",
+                    GeoJson::from(vec![
+                        self.mercator.to_wgs84_gj(&pts[start]),
+                        self.mercator.to_wgs84_gj(&pts[end]),
+                    ]),
+                );
+
+                // At the ends of the corridor, there's no crossing on that side
+                let mut crossings = Vec::new();
+                for (key, idx) in [("crossing_before", start), ("crossing_after", end)] {
+                    if is_crossing(idx) {
+                        f.set_property(key, corridor.nodes[idx].0);
+                        crossings.push(self.mercator.to_wgs84_gj(&pts[idx]));
+                    } else {
+                        f.set_property(key, None::<i64>);
+                    }
+                }
+                f.set_property("crossings", GeoJson::from(crossings));
+
+                features.push(f);
+            }
+        }
+
+        Ok(serde_json::to_string(&GeoJson::from(features))?)
+    }
+
+    /// Join severance roads with the same name end to end. Where more than two ways meet, like at
+    /// a fork, the corridor stops.
+    fn find_corridors(&self) -> Vec<Corridor> {
+        let mut ways_per_name: BTreeMap<Option<&String>, Vec<WayID>> = BTreeMap::new();
+        for (id, way) in &self.derived_ways {
+            if way.kind.is_road() && way.is_severance() {
+                ways_per_name
+                    .entry(way.tags.get("name"))
+                    .or_default()
+                    .push(*id);
+            }
+        }
+
+        let mut corridors = Vec::new();
+        for (name, mut ways) in ways_per_name {
+            ways.sort();
+            let mut ways_per_endpoint: HashMap<NodeID, Vec<WayID>> = HashMap::new();
+            for w in &ways {
+                let node_ids = &self.derived_ways[w].node_ids;
+                ways_per_endpoint.entry(node_ids[0]).or_default().push(*w);
+                ways_per_endpoint
+                    .entry(*node_ids.last().unwrap())
+                    .or_default()
+                    .push(*w);
+            }
+
+            let mut used = HashSet::new();
+            for w in ways {
+                if !used.insert(w) {
+                    continue;
+                }
+                let mut corridor = Corridor {
+                    name: name.cloned(),
+                    ways: vec![w],
+                    nodes: self.derived_ways[&w].node_ids.clone(),
+                };
+                self.extend_corridor(&mut corridor, &ways_per_endpoint, &mut used);
+                corridor.nodes.reverse();
+                corridor.ways.reverse();
+                self.extend_corridor(&mut corridor, &ways_per_endpoint, &mut used);
+                // Keep the direction of the first way
+                corridor.nodes.reverse();
+                corridor.ways.reverse();
+                corridors.push(corridor);
+            }
+        }
+        corridors
+    }
+
+    /// Keep adding ways to the last node of the corridor, while there's exactly one choice
+    fn extend_corridor(
+        &self,
+        corridor: &mut Corridor,
+        ways_per_endpoint: &HashMap<NodeID, Vec<WayID>>,
+        used: &mut HashSet<WayID>,
+    ) {
+        loop {
+            let last = *corridor.nodes.last().unwrap();
+            let candidates: Vec<WayID> = ways_per_endpoint[&last]
+                .iter()
+                .filter(|w| !used.contains(*w))
+                .cloned()
+                .collect();
+            if candidates.len() != 1 {
+                return;
+            }
+            let next = candidates[0];
+            used.insert(next);
+            corridor.ways.push(next);
+
+            let mut node_ids = self.derived_ways[&next].node_ids.clone();
+            if node_ids[0] != last {
+                node_ids.reverse();
+            }
+            corridor.nodes.extend(node_ids.into_iter().skip(1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crossing_spacing() {
+        // A 500m road split into two ways, with a crossing 100m along it
+        let osm = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <node id="1" lon="0.000000" lat="0.000000" version="1" />
+  <node id="2" lon="0.000898" lat="0.000000" version="1">
+    <tag k="highway" v="crossing"/>
+  </node>
+  <node id="3" lon="0.002695" lat="0.000000" version="1" />
+  <node id="4" lon="0.004492" lat="0.000000" version="1" />
+  <way id="100" version="1">
+    <nd ref="1"/><nd ref="2"/><nd ref="3"/>
+    <tag k="highway" v="primary"/>
+    <tag k="name" v="Main Road"/>
+  </way>
+  <way id="101" version="1">
+    <nd ref="4"/><nd ref="3"/>
+    <tag k="highway" v="primary"/>
+    <tag k="name" v="Main Road"/>
+  </way>
+</osm>"#;
+        let model = Speedwalk::new_from_osm(osm.as_bytes(), None).unwrap();
+        let gj: GeoJson = model
+            .audit_crossing_spacing(SpacingOptions::default())
+            .unwrap()
+            .parse()
+            .unwrap();
+        let GeoJson::FeatureCollection(fc) = gj else {
+            panic!("expected a FeatureCollection");
+        };
+        assert_eq!(fc.features.len(), 1);
+        let f = &fc.features[0];
+        assert_eq!(f.property("crossing_before").unwrap(), 2);
+        assert!(f.property("crossing_after").unwrap().is_null());
+        let length = f.property("length").unwrap().as_f64().unwrap();
+        assert!((length - 400.0).abs() < 1.0, "length {length}");
+    }
+}
@@ -0,0 +1,315 @@
+use osm_reader::{NodeID, WayID};
+use utils::Tags;
+
+use crate::{Speedwalk, TagFix, audit::crossing_control, problems::ProblemScope};
+
+/// A crossing way whose tags disagree with a crossing node on it
+pub(crate) struct InconsistentCrossing {
+    pub way: WayID,
+    pub node: NodeID,
+    /// The control type each says
+    pub way_control: &'static str,
+    pub node_control: &'static str,
+    /// Make the way match the node
+    pub fix: TagFix,
+}
+
+type ImpliedTags = &'static [(&'static str, &'static str)];
+
+/// Legacy `crossing` values, the modern value replacing each (if the legacy value doesn't say),
+/// and the tags each implies
+const LEGACY_CROSSING_VALUES: [(&str, Option<&str>, ImpliedTags); 7] = [
+    (
+        "zebra",
+        Some("uncontrolled"),
+        &[("crossing:markings", "zebra")],
+    ),
+    (
+        "marked",
+        Some("uncontrolled"),
+        &[("crossing:markings", "yes")],
+    ),
+    ("island", None, &[("crossing:island", "yes")]),
+    (
+        "pelican",
+        Some("traffic_signals"),
+        &[("crossing_ref", "pelican"), ("crossing:markings", "yes")],
+    ),
+    (
+        "toucan",
+        Some("traffic_signals"),
+        &[("crossing_ref", "toucan"), ("crossing:markings", "yes")],
+    ),
+    (
+        "pegasus",
+        Some("traffic_signals"),
+        &[("crossing_ref", "pegasus"), ("crossing:markings", "yes")],
+    ),
+    (
+        "puffin",
+        Some("traffic_signals"),
+        &[("crossing_ref", "puffin"), ("crossing:markings", "yes")],
+    ),
+];
+
+/// For a legacy `crossing` value, returns the modern value replacing it (or None to remove it) and
+/// extra tags implied by it
+pub(crate) fn upgrade_legacy_crossing(value: &str) -> Option<(Option<&'static str>, ImpliedTags)> {
+    LEGACY_CROSSING_VALUES
+        .iter()
+        .find(|(legacy, _, _)| *legacy == value)
+        .map(|(_, new_value, implied)| (*new_value, *implied))
+}
+
+/// Deprecated crossing tags on a node or way. Returns a note for each deprecated tag, and one fix
+/// replacing all of them.
+pub(crate) fn deprecated_crossing_tags(tags: &Tags) -> Option<(Vec<String>, TagFix)> {
+    let mut notes = Vec::new();
+    let mut fix = TagFix::default();
+
+    if let Some(value) = tags.get("crossing")
+        && let Some((new_value, implied)) = upgrade_legacy_crossing(value)
+    {
+        notes.push(format!("crossing={value} is deprecated"));
+        match new_value {
+            Some(new_value) => fix.add_tags.push(tag("crossing", new_value)),
+            None => {
+                fix.remove_keys.push("crossing".to_string());
+                // Keep it a crossing node
+                if !tags.has("highway") {
+                    fix.add_tags.push(tag("highway", "crossing"));
+                }
+            }
+        }
+        for (k, v) in implied {
+            if !tags.has(k) {
+                fix.add_tags.push(tag(k, v));
+            }
+        }
+    } else if let Some(crossing_ref) = tags.get("crossing_ref")
+        && !tags.has("crossing:markings")
+    {
+        notes.push("crossing_ref without crossing:markings".to_string());
+        fix.add_tags.push(tag(
+            "crossing:markings",
+            if crossing_ref == "zebra" {
+                "zebra"
+            } else {
+                "yes"
+            },
+        ));
+    }
+
+    if notes.is_empty() {
+        None
+    } else {
+        Some((notes, fix))
+    }
+}
+
+fn tag(k: &str, v: &str) -> (String, String) {
+    (k.to_string(), v.to_string())
+}
+
+impl Speedwalk {
+    /// Crossing ways in scope whose control type disagrees with a crossing node where they meet a
+    /// road. Only the first disagreeing node on each way is returned. Sorted by way.
+    pub(crate) fn find_inconsistent_crossings(
+        &self,
+        scope: Option<&ProblemScope>,
+    ) -> Vec<InconsistentCrossing> {
+        let mut ways: Vec<_> = self
+            .derived_ways
+            .iter()
+            .filter(|(_, way)| {
+                way.tags.is("footway", "crossing") && scope.is_none_or(|s| s.has_way(way))
+            })
+            .collect();
+        ways.sort_by_key(|(id, _)| **id);
+
+        let mut results = Vec::new();
+        for (way_id, way) in ways {
+            let way_control = crossing_control(&way.tags);
+            if way_control == "unknown" {
+                continue;
+            }
+            for node_id in &way.node_ids {
+                let node = &self.derived_nodes[node_id];
+                if !node.is_crossing()
+                    || !node
+                        .way_ids
+                        .iter()
+                        .any(|w| self.derived_ways[w].kind.is_road())
+                {
+                    continue;
+                }
+                let node_control = crossing_control(&node.tags);
+                if node_control == "unknown" || node_control == way_control {
+                    continue;
+                }
+
+                let mut fix = TagFix {
+                    remove_keys: Vec::new(),
+                    add_tags: vec![("crossing".to_string(), node_control.to_string())],
+                };
+                let keys = ["crossing:markings", "crossing:signals"];
+                for key in keys {
+                    if let Some(value) = node.tags.get(key) {
+                        fix.add_tags.push((key.to_string(), value.clone()));
+                    }
+                }
+                // Only remove tags from the way if they'd still disagree
+                if crossing_control(&fix.apply(&way.tags)) != node_control {
+                    for key in keys {
+                        if way.tags.has(key) && !node.tags.has(key) {
+                            fix.remove_keys.push(key.to_string());
+                        }
+                    }
+                }
+                results.push(InconsistentCrossing {
+                    way: *way_id,
+                    node: *node_id,
+                    way_control,
+                    node_control,
+                    fix,
+                });
+                break;
+            }
+        }
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ProblemType;
+
+    #[test]
+    fn test_deprecated_crossing_tags() {
+        let mut tags = Tags::empty();
+        tags.insert("highway", "crossing");
+        tags.insert("crossing", "zebra");
+        let (notes, fix) = deprecated_crossing_tags(&tags).unwrap();
+        assert_eq!(notes, vec!["crossing=zebra is deprecated"]);
+        assert_eq!(
+            fix.add_tags,
+            vec![
+                ("crossing".to_string(), "uncontrolled".to_string()),
+                ("crossing:markings".to_string(), "zebra".to_string()),
+            ]
+        );
+
+        let mut tags = Tags::empty();
+        tags.insert("crossing", "island");
+        let (_, fix) = deprecated_crossing_tags(&tags).unwrap();
+        assert_eq!(fix.remove_keys, vec!["crossing".to_string()]);
+
+        // Upgraded legacy values aren't deprecated themselves
+        for legacy in ["zebra", "marked", "island", "pelican", "toucan"] {
+            let mut tags = Tags::empty();
+            tags.insert("highway", "crossing");
+            tags.insert("crossing", legacy);
+            let (_, fix) = deprecated_crossing_tags(&tags).unwrap();
+            assert!(deprecated_crossing_tags(&fix.apply(&tags)).is_none());
+        }
+
+        let mut tags = Tags::empty();
+        tags.insert("crossing", "uncontrolled");
+        tags.insert("crossing:markings", "zebra");
+        assert!(deprecated_crossing_tags(&tags).is_none());
+    }
+
+    #[test]
+    fn test_inconsistent_crossings() {
+        let osm = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <node id="1" lon="0.000000" lat="0.000000" version="1" />
+  <node id="2" lon="0.000450" lat="0.000000" version="1">
+    <tag k="highway" v="crossing"/>
+    <tag k="crossing" v="traffic_signals"/>
+  </node>
+  <node id="3" lon="0.000900" lat="0.000000" version="1" />
+  <node id="4" lon="0.000450" lat="-0.000100" version="1" />
+  <node id="5" lon="0.000450" lat="0.000100" version="1" />
+  <way id="100" version="1">
+    <nd ref="1"/><nd ref="2"/><nd ref="3"/>
+    <tag k="highway" v="residential"/>
+  </way>
+  <way id="200" version="1">
+    <nd ref="4"/><nd ref="2"/><nd ref="5"/>
+    <tag k="highway" v="footway"/>
+    <tag k="footway" v="crossing"/>
+    <tag k="crossing" v="uncontrolled"/>
+    <tag k="crossing:markings" v="zebra"/>
+  </way>
+</osm>"#;
+        let mut model = Speedwalk::new_from_osm(osm.as_bytes(), None).unwrap();
+        let found = model.find_inconsistent_crossings(None);
+        assert_eq!(found.len(), 1);
+        assert_eq!((found[0].way, found[0].node), (WayID(200), NodeID(2)));
+        assert_eq!(
+            found[0].fix,
+            TagFix {
+                remove_keys: Vec::new(),
+                add_tags: vec![("crossing".to_string(), "traffic_signals".to_string())],
+            }
+        );
+
+        let problem = model.derived_ways[&WayID(200)]
+            .problems
+            .iter()
+            .find(|p| p.problem_type == ProblemType::InconsistentCrossingTags)
+            .unwrap();
+        let fix = problem.tag_fix.clone().unwrap();
+
+        let mut edits = model.take_edits();
+        edits
+            .apply_cmd(
+                crate::UserCmd::SetTags {
+                    way: WayID(200),
+                    remove_keys: fix.remove_keys,
+                    add_tags: fix.add_tags,
+                },
+                &model,
+            )
+            .unwrap();
+        model.set_edits(edits);
+        model.after_edit();
+        assert!(model.find_inconsistent_crossings(None).is_empty());
+    }
+
+    #[test]
+    fn test_fix_deprecated_node_tags() {
+        let osm = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <node id="1" lon="0.000000" lat="0.000000" version="1" />
+  <node id="2" lon="0.000450" lat="0.000000" version="3">
+    <tag k="highway" v="crossing"/>
+    <tag k="crossing" v="zebra"/>
+  </node>
+  <node id="3" lon="0.000900" lat="0.000000" version="1" />
+  <way id="100" version="1">
+    <nd ref="1"/><nd ref="2"/><nd ref="3"/>
+    <tag k="highway" v="residential"/>
+  </way>
+</osm>"#;
+        let mut model = Speedwalk::new_from_osm(osm.as_bytes(), None).unwrap();
+        let cmds = model.tag_fix_cmds("deprecated_crossing_tags");
+        assert_eq!(cmds.len(), 1);
+
+        let mut edits = model.take_edits();
+        edits.apply_cmds_without_rebuild(cmds, &model).unwrap();
+        model.set_edits(edits);
+        model.after_edit();
+
+        let node = &model.derived_nodes[&NodeID(2)];
+        assert!(node.tags.is("crossing", "uncontrolled"));
+        assert!(node.tags.is("crossing:markings", "zebra"));
+        assert!(node.problems.is_empty());
+
+        let edits = model.take_edits();
+        let osc = edits.to_osc(&model);
+        assert!(osc.contains(r#"<node id="2" version="3""#));
+    }
+}
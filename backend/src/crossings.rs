@@ -1,20 +1,306 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use geo::line_intersection::{LineIntersection, line_intersection};
 use geo::{
-    BoundingRect, Closest, ClosestPoint, Coord, Distance, Euclidean, Intersects, Line, LineString,
-    Point, Polygon,
+    BoundingRect, Closest, ClosestPoint, Coord, Distance, Euclidean, InterpolatePoint, Intersects,
+    Line, LineString, Point, Polygon,
 };
-use osm_reader::WayID;
+use osm_reader::{NodeID, WayID};
 use rstar::{AABB, RTree, primitives::GeomWithData};
+use serde::{Deserialize, Serialize};
 use utils::Tags;
 
-use crate::{Kind, Node, Speedwalk, edits::CreateNewGeometry};
+use crate::{
+    Kind, Node, Provenance, Side, Speedwalk, UserCmd,
+    crossing_tags::upgrade_legacy_crossing,
+    edits::CreateNewGeometry,
+    make_sidewalks::{BUFFER_DISTANCE, side_tagged_yes},
+    problems::{PROBLEM_RADIUS, ProblemScope, ProblemType},
+};
+
+/// Crossing nodes on two one-way carriageways this close together are treated as one crossing
+/// with a traffic island between them.
+const MAX_DUAL_CARRIAGEWAY_GAP: f64 = 25.0;
+/// Where a crossing node is tagged with an island, the island starts this far past the node, and
+/// is twice as long
+const ISLAND_HALF_LENGTH: f64 = 1.0;
+/// Between dual carriageways, how far the island starts from each carriageway's center
+const CARRIAGEWAY_HALF_WIDTH: f64 = 3.0;
+/// When a road is only tagged with `lanes`, assume each one is this wide
+const LANE_WIDTH: f64 = 3.0;
+/// Used by default for `max_snap_distance_meters`, and when snapping manually drawn crossings
+pub const DEFAULT_MAX_SNAP_DISTANCE_METERS: f64 = 25.0;
+
+/// A crossing node on a road whose sidewalks are only tagged, not mapped separately
+pub(crate) struct VirtualCrossing {
+    pub node: NodeID,
+    pub road: WayID,
+    /// Where the implicit sidewalk on each side of the road would be
+    pub left: Coord,
+    pub right: Coord,
+    /// The sides with a sidewalk tagged
+    pub tagged_sides: Vec<Side>,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct CrossingOptions {
+    /// Tags copied from the crossing node onto the generated crossing way
+    #[serde(default = "default_transfer_tags")]
+    pub transfer_tags: Vec<String>,
+    /// Rewrite legacy `crossing` values on the way, like `crossing=zebra` to
+    /// `crossing=uncontrolled` and `crossing:markings=zebra`. The node itself isn't changed.
+    #[serde(default = "default_true")]
+    pub upgrade_legacy_values: bool,
+    /// How far to look straight out from each side of a crossing node for a sidewalk
+    #[serde(default = "default_probe_distance")]
+    pub probe_distance_meters: f64,
+    /// When nothing is straight out from the crossing node, look for the closest sidewalk to a
+    /// point this far to the side
+    #[serde(default = "default_side_offset")]
+    pub side_offset_meters: f64,
+    /// A crossing node on two roads is only connected when the roads are within this angle of
+    /// each other
+    #[serde(default = "default_parallel_tolerance")]
+    pub parallel_tolerance_degrees: f64,
+    /// Never connect a crossing to a sidewalk farther away than this
+    #[serde(default = "default_max_snap_distance")]
+    pub max_snap_distance_meters: f64,
+    /// When the road's `width` or `lanes` is known, measure the distances above from the edge of
+    /// the road instead of the center
+    #[serde(default = "default_true")]
+    pub road_width_aware: bool,
+}
+
+impl Default for CrossingOptions {
+    fn default() -> Self {
+        Self {
+            transfer_tags: default_transfer_tags(),
+            upgrade_legacy_values: true,
+            probe_distance_meters: default_probe_distance(),
+            side_offset_meters: default_side_offset(),
+            parallel_tolerance_degrees: default_parallel_tolerance(),
+            max_snap_distance_meters: default_max_snap_distance(),
+            road_width_aware: true,
+        }
+    }
+}
+
+impl CrossingOptions {
+    fn probe(&self, road: &Tags) -> Probe {
+        let half_width = if self.road_width_aware {
+            road_width(road).unwrap_or(0.0) / 2.0
+        } else {
+            0.0
+        };
+        Probe {
+            distance: half_width + self.probe_distance_meters,
+            side_offset: half_width + self.side_offset_meters,
+            max_snap_distance: half_width + self.max_snap_distance_meters,
+        }
+    }
+}
+
+/// How far to search from one crossing node
+struct Probe {
+    distance: f64,
+    side_offset: f64,
+    max_snap_distance: f64,
+}
+
+/// Meters, from `width` or `lanes`
+fn road_width(tags: &Tags) -> Option<f64> {
+    if let Some(width) = tags
+        .get("width")
+        .and_then(|w| w.trim_end_matches('m').trim().parse::<f64>().ok())
+    {
+        return Some(width);
+    }
+    let lanes = tags.get("lanes")?.parse::<f64>().ok()?;
+    Some(lanes * LANE_WIDTH)
+}
+
+fn default_probe_distance() -> f64 {
+    10.0
+}
+
+fn default_side_offset() -> f64 {
+    5.0
+}
+
+fn default_parallel_tolerance() -> f64 {
+    10.0
+}
+
+fn default_max_snap_distance() -> f64 {
+    DEFAULT_MAX_SNAP_DISTANCE_METERS
+}
+
+fn default_transfer_tags() -> Vec<String> {
+    [
+        "crossing",
+        "crossing:markings",
+        "crossing:signals",
+        "tactile_paving",
+        "button_operated",
+        "traffic_signals:sound",
+        "kerb",
+    ]
+    .into_iter()
+    .map(|k| k.to_string())
+    .collect()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// What happened when trying to connect a crossing node to sidewalks
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CrossingOutcome {
+    Connected,
+    /// No sidewalk found on this side of the road
+    NoSidewalk(Side),
+    /// The only sidewalk found on this side is past another road
+    BlockedByRoad(Side),
+    /// The only sidewalk found on this side is past a building
+    BlockedByBuilding(Side),
+    /// Both sides found the same point on a sidewalk
+    EndpointsIdentical,
+    /// The node is where a driveway or another road meets this one
+    Driveway,
+}
+
+impl CrossingOutcome {
+    /// None when connected
+    pub fn problem_type(self) -> Option<ProblemType> {
+        match self {
+            CrossingOutcome::Connected => None,
+            CrossingOutcome::NoSidewalk(_) => Some(ProblemType::CrossingNoSidewalk),
+            CrossingOutcome::BlockedByRoad(_) => Some(ProblemType::CrossingBlockedByRoad),
+            CrossingOutcome::BlockedByBuilding(_) => Some(ProblemType::CrossingBlockedByBuilding),
+            CrossingOutcome::EndpointsIdentical => Some(ProblemType::CrossingEndpointsIdentical),
+            CrossingOutcome::Driveway => Some(ProblemType::CrossingAtRoadJunction),
+        }
+    }
+
+    /// The problem's description, saying which side it's on
+    pub fn describe(self) -> String {
+        let Some(problem_type) = self.problem_type() else {
+            return "crossing connected to sidewalks".to_string();
+        };
+        match self {
+            CrossingOutcome::NoSidewalk(side)
+            | CrossingOutcome::BlockedByRoad(side)
+            | CrossingOutcome::BlockedByBuilding(side) => {
+                format!("{} (on the {})", problem_type.description(), side.key())
+            }
+            _ => problem_type.description().to_string(),
+        }
+    }
+}
+
+type NewWays = Vec<(LineString, Tags, Provenance)>;
+/// The sidewalk and point on it at each end of a new crossing
+type Endpoints = [(WayID, Coord); 2];
 
 impl Speedwalk {
-    pub fn connect_all_crossings(&self, include_crossing_no: bool) -> CreateNewGeometry {
+    pub fn connect_all_crossings(
+        &self,
+        include_crossing_no: bool,
+        options: &CrossingOptions,
+    ) -> CreateNewGeometry {
+        self.generate_crossings(include_crossing_no, options, None)
+            .0
+    }
+
+    /// For every crossing node that isn't connected to anything yet, what would happen with
+    /// `connect_all_crossings`
+    pub fn crossing_outcomes(
+        &self,
+        include_crossing_no: bool,
+        options: &CrossingOptions,
+    ) -> Vec<(NodeID, CrossingOutcome)> {
+        self.generate_crossings(include_crossing_no, options, None)
+            .1
+    }
+
+    /// Like `crossing_outcomes`, but only for crossing nodes in scope
+    pub(crate) fn crossing_outcomes_near(
+        &self,
+        options: &CrossingOptions,
+        scope: Option<&ProblemScope>,
+    ) -> Vec<(NodeID, CrossingOutcome)> {
+        self.generate_crossings(false, options, scope).1
+    }
+
+    /// The options of the most recent `ConnectAllCrossings` edit, or the defaults if there isn't
+    /// one
+    pub(crate) fn last_crossing_options(&self) -> CrossingOptions {
+        self.edits
+            .iter()
+            .flat_map(|edits| edits.user_commands.iter())
+            .rev()
+            .find_map(|cmd| match cmd {
+                UserCmd::ConnectAllCrossings(_, options) => Some(options.clone()),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    /// Crossing nodes on roads with sidewalks tagged on at least one side, where no separate
+    /// footway is mapped through the node. Sorted by node.
+    pub(crate) fn virtual_crossings(&self) -> Vec<VirtualCrossing> {
+        let mut results = Vec::new();
+        for (id, node) in &self.derived_nodes {
+            if !node.is_crossing()
+                || !node
+                    .way_ids
+                    .iter()
+                    .all(|w| self.derived_ways[w].kind.is_road())
+            {
+                continue;
+            }
+            let mut roads = node.way_ids.clone();
+            roads.sort();
+            let Some(road_id) = roads.into_iter().find(|w| {
+                let road = &self.derived_ways[w];
+                road.kind == Kind::RoadWithTags
+                    && (side_tagged_yes(&road.tags, Side::Left)
+                        || side_tagged_yes(&road.tags, Side::Right))
+            }) else {
+                continue;
+            };
+            let road = &self.derived_ways[&road_id];
+
+            // Match where generated sidewalks would go, unless the road is wider
+            let half_width = road_width(&road.tags)
+                .map(|w| w / 2.0)
+                .unwrap_or(BUFFER_DISTANCE);
+            let angle = angle_of_pt_on_line(&road.linestring, node.pt);
+            results.push(VirtualCrossing {
+                node: *id,
+                road: road_id,
+                left: project_away(node.pt, angle - 90.0, half_width),
+                right: project_away(node.pt, angle + 90.0, half_width),
+                tagged_sides: [Side::Left, Side::Right]
+                    .into_iter()
+                    .filter(|side| side_tagged_yes(&road.tags, *side))
+                    .collect(),
+            });
+        }
+        results.sort_by_key(|x| x.node);
+        results
+    }
+
+    fn generate_crossings(
+        &self,
+        include_crossing_no: bool,
+        options: &CrossingOptions,
+        scope: Option<&ProblemScope>,
+    ) -> (CreateNewGeometry, Vec<(NodeID, CrossingOutcome)>) {
         info!("Finding crossings to connect");
         let mut crossings = Vec::new();
+        let mut outcomes = Vec::new();
         for (id, node) in &self.derived_nodes {
             // When do we generate a crossing way from a node? Have tried a few heuristics here:
             //
@@ -28,6 +314,11 @@ impl Speedwalk {
             // - if the node is only attached to one way (in the middle), it needs a crossing
             // - if the node is attached to two ways AND those ways are nearly
             //   parallel/anti-parallel, then it needs a crossing
+            //
+            // Nodes just outside the scope might pair up with one inside over a dual carriageway
+            if scope.is_some_and(|s| !s.near_node(node, MAX_DUAL_CARRIAGEWAY_GAP)) {
+                continue;
+            }
             if node.is_crossing() || (include_crossing_no && node.is_explicit_crossing_no()) {
                 let ways = node
                     .way_ids
@@ -39,97 +330,372 @@ impl Speedwalk {
                 } else if ways.len() == 2
                     && ways[0].kind.is_road()
                     && ways[1].kind.is_road()
-                    && nearly_parallel(&ways[0].linestring, &ways[1].linestring, 10.0)
+                    && nearly_parallel(
+                        &ways[0].linestring,
+                        &ways[1].linestring,
+                        options.parallel_tolerance_degrees,
+                    )
                 {
                     crossings.push(*id);
+                } else if ways.len() > 1 && ways.iter().all(|way| way.kind.is_road()) {
+                    outcomes.push((*id, CrossingOutcome::Driveway));
                 }
+                // Otherwise, a footway or something already reaches the node
             }
         }
 
         info!("Building rtrees for up to {} ways", self.derived_ways.len());
+        let nearby_ways = || {
+            self.derived_ways.iter().filter(|(_, way)| {
+                scope.is_none_or(|s| s.near_way(way, MAX_DUAL_CARRIAGEWAY_GAP + PROBLEM_RADIUS))
+            })
+        };
         let closest_sidewalk = RTree::bulk_load(
-            self.derived_ways
-                .iter()
+            nearby_ways()
                 .filter(|(_, way)| way.kind == Kind::Sidewalk || way.is_walkable_other())
                 .map(|(id, way)| GeomWithData::new(way.linestring.clone(), *id))
                 .collect(),
         );
         let closest_line = RTree::bulk_load(
-            self.derived_ways
-                .iter()
+            nearby_ways()
                 .map(|(id, way)| GeomWithData::new(way.linestring.clone(), *id))
                 .collect(),
         );
 
-        info!("Generating {} crossings", crossings.len());
+        let (pairs, crossings) = self.find_dual_carriageway_crossings(crossings);
+
+        info!(
+            "Generating {} crossings and {} crossings over dual carriageways",
+            crossings.len(),
+            pairs.len()
+        );
         let mut new_crossings = Vec::new();
         let mut insert_new_nodes = HashMap::new();
-        for crossing_node_id in crossings {
-            let crossing_node = &self.derived_nodes[&crossing_node_id];
-            let crossing_pt = crossing_node.pt;
-
-            // Make a perpendicular line at the node
-            let road_way_id = crossing_node.way_ids[0];
-            let road_linestring = &self.derived_ways[&road_way_id].linestring;
-            let angle = angle_of_pt_on_line(road_linestring, crossing_pt);
-
-            let Some((sidewalk1, endpt1)) = find_sidewalk_hit(
+        let mut results = Vec::new();
+        for id in crossings {
+            let result = self.connect_crossing(id, &closest_sidewalk, &closest_line, options);
+            results.push((vec![id], result));
+        }
+        for (id1, id2) in pairs {
+            let result = self.connect_dual_carriageway_crossing(
+                (id1, id2),
                 &closest_sidewalk,
                 &closest_line,
-                &self.closest_building,
-                crossing_node,
-                angle + 90.0,
-            ) else {
-                continue;
-            };
+                options,
+            );
+            results.push((vec![id1, id2], result));
+        }
 
-            let Some((sidewalk2, endpt2)) = find_sidewalk_hit(
-                &closest_sidewalk,
-                &closest_line,
-                &self.closest_building,
-                crossing_node,
-                angle - 90.0,
-            ) else {
-                continue;
+        for (ids, result) in results {
+            let outcome = match result {
+                Ok((new_ways, endpoints)) => {
+                    new_crossings.extend(new_ways);
+                    for (sidewalk, endpt) in endpoints {
+                        insert_new_nodes
+                            .entry(sidewalk)
+                            .or_insert_with(Vec::new)
+                            .push((endpt, Tags::empty()));
+                    }
+                    CrossingOutcome::Connected
+                }
+                Err(outcome) => outcome,
             };
+            for id in ids {
+                outcomes.push((id, outcome));
+            }
+        }
+        info!("Successfully made {} crossings", new_crossings.len());
+        if let Some(scope) = scope {
+            outcomes.retain(|(id, _)| scope.has_node(&self.derived_nodes[id]));
+        }
 
-            // If both sides snapped to the same place, skip it. The same sidewalk way could
-            // stretch very far to both sides, so check distance of the snapped points instead.
-            if Euclidean.distance(endpt1, endpt2) < 1.0 {
-                continue;
+        (
+            CreateNewGeometry {
+                new_ways: new_crossings,
+                new_kind: Kind::Crossing,
+                insert_new_nodes,
+                modify_existing_way_tags: HashMap::new(),
+            },
+            outcomes,
+        )
+    }
+
+    fn connect_crossing(
+        &self,
+        crossing_node_id: NodeID,
+        closest_sidewalk: &RTree<GeomWithData<LineString, WayID>>,
+        closest_line: &RTree<GeomWithData<LineString, WayID>>,
+        options: &CrossingOptions,
+    ) -> Result<(NewWays, Endpoints), CrossingOutcome> {
+        let crossing_node = &self.derived_nodes[&crossing_node_id];
+        let crossing_pt = crossing_node.pt;
+
+        // Make a perpendicular line at the node
+        let road_way_id = crossing_node.way_ids[0];
+        let road = &self.derived_ways[&road_way_id];
+        let angle = angle_of_pt_on_line(&road.linestring, crossing_pt);
+        let probe = options.probe(&road.tags);
+
+        let (sidewalk1, endpt1) = find_sidewalk_hit(
+            closest_sidewalk,
+            closest_line,
+            &self.closest_building,
+            crossing_node,
+            angle + 90.0,
+            Side::Right,
+            &probe,
+        )?;
+        let (sidewalk2, endpt2) = find_sidewalk_hit(
+            closest_sidewalk,
+            closest_line,
+            &self.closest_building,
+            crossing_node,
+            angle - 90.0,
+            Side::Left,
+            &probe,
+        )?;
+
+        // If both sides snapped to the same place, skip it. The same sidewalk way could
+        // stretch very far to both sides, so check distance of the snapped points instead.
+        if Euclidean.distance(endpt1, endpt2) < 1.0 {
+            return Err(CrossingOutcome::EndpointsIdentical);
+        }
+
+        let provenance = Provenance {
+            osm_node: Some(crossing_node_id),
+            osm_way: Some(road_way_id),
+            side: None,
+        };
+        let new_ways = if has_island(&crossing_node.tags) {
+            // The road is only mapped down its middle, so put the island just past it. The
+            // crossing node stays on the first crossing segment.
+            let island1 = toward(crossing_pt, endpt2, ISLAND_HALF_LENGTH);
+            let island2 = toward(island1, endpt2, 2.0 * ISLAND_HALF_LENGTH);
+            vec![
+                (
+                    LineString::new(vec![endpt1, crossing_pt, island1]),
+                    crossing_way_tags(crossing_node, options),
+                    provenance.clone(),
+                ),
+                (
+                    LineString::new(vec![island1, island2]),
+                    traffic_island_tags(),
+                    provenance.clone(),
+                ),
+                (
+                    LineString::new(vec![island2, endpt2]),
+                    crossing_way_tags(crossing_node, options),
+                    provenance,
+                ),
+            ]
+        } else {
+            vec![(
+                LineString::new(vec![endpt1, crossing_pt, endpt2]),
+                crossing_way_tags(crossing_node, options),
+                provenance,
+            )]
+        };
+        Ok((new_ways, [(sidewalk1, endpt1), (sidewalk2, endpt2)]))
+    }
+
+    fn connect_dual_carriageway_crossing(
+        &self,
+        (id1, id2): (NodeID, NodeID),
+        closest_sidewalk: &RTree<GeomWithData<LineString, WayID>>,
+        closest_line: &RTree<GeomWithData<LineString, WayID>>,
+        options: &CrossingOptions,
+    ) -> Result<(NewWays, Endpoints), CrossingOutcome> {
+        let node1 = &self.derived_nodes[&id1];
+        let node2 = &self.derived_nodes[&id2];
+
+        // Head away from the other carriageway to find the sidewalk on each side
+        let angle = angle_of_line(Line::new(node2.pt, node1.pt));
+        let road_angle =
+            angle_of_pt_on_line(&self.derived_ways[&node1.way_ids[0]].linestring, node1.pt);
+        let side1 = if shortest_rotation(angle, road_angle) > 0.0 {
+            Side::Right
+        } else {
+            Side::Left
+        };
+        let side2 = if side1 == Side::Left {
+            Side::Right
+        } else {
+            Side::Left
+        };
+        let (sidewalk1, endpt1) = find_sidewalk_hit(
+            closest_sidewalk,
+            closest_line,
+            &self.closest_building,
+            node1,
+            angle,
+            side1,
+            &options.probe(&self.derived_ways[&node1.way_ids[0]].tags),
+        )?;
+        let (sidewalk2, endpt2) = find_sidewalk_hit(
+            closest_sidewalk,
+            closest_line,
+            &self.closest_building,
+            node2,
+            angle + 180.0,
+            side2,
+            &options.probe(&self.derived_ways[&node2.way_ids[0]].tags),
+        )?;
+        if Euclidean.distance(endpt1, endpt2) < 1.0 {
+            return Err(CrossingOutcome::EndpointsIdentical);
+        }
+
+        let island1 = toward(node1.pt, node2.pt, CARRIAGEWAY_HALF_WIDTH);
+        let island2 = toward(node2.pt, node1.pt, CARRIAGEWAY_HALF_WIDTH);
+        let provenance = |id: NodeID, node: &Node| Provenance {
+            osm_node: Some(id),
+            osm_way: Some(node.way_ids[0]),
+            side: None,
+        };
+        let new_ways = vec![
+            (
+                LineString::new(vec![endpt1, node1.pt, island1]),
+                crossing_way_tags(node1, options),
+                provenance(id1, node1),
+            ),
+            (
+                LineString::new(vec![island1, island2]),
+                traffic_island_tags(),
+                provenance(id1, node1),
+            ),
+            (
+                LineString::new(vec![island2, node2.pt, endpt2]),
+                crossing_way_tags(node2, options),
+                provenance(id2, node2),
+            ),
+        ];
+        Ok((new_ways, [(sidewalk1, endpt1), (sidewalk2, endpt2)]))
+    }
+}
+
+impl Speedwalk {
+    /// Finds pairs of crossing nodes facing each other on two one-way carriageways of the same
+    /// road. Returns those pairs and all the remaining crossings.
+    fn find_dual_carriageway_crossings(
+        &self,
+        crossings: Vec<NodeID>,
+    ) -> (Vec<(NodeID, NodeID)>, Vec<NodeID>) {
+        let oneway: Vec<GeomWithData<Point, NodeID>> = crossings
+            .iter()
+            .filter(|n| {
+                let node = &self.derived_nodes[n];
+                node.way_ids.len() == 1
+                    && self.derived_ways[&node.way_ids[0]]
+                        .tags
+                        .is_any("oneway", vec!["yes", "1", "-1"])
+            })
+            .map(|n| GeomWithData::new(Point::from(self.derived_nodes[n].pt), *n))
+            .collect();
+        let rtree = RTree::bulk_load(oneway.clone());
+
+        // (distance, node1, node2)
+        let mut candidates = Vec::new();
+        for obj1 in &oneway {
+            let node1 = &self.derived_nodes[&obj1.data];
+            let road1 = node1.way_ids[0];
+            let road_angle1 = angle_of_pt_on_line(&self.derived_ways[&road1].linestring, node1.pt);
+            for obj2 in rtree.locate_within_distance(
+                *obj1.geom(),
+                MAX_DUAL_CARRIAGEWAY_GAP * MAX_DUAL_CARRIAGEWAY_GAP,
+            ) {
+                let node2 = &self.derived_nodes[&obj2.data];
+                let road2 = node2.way_ids[0];
+                if obj2.data <= obj1.data || road2 == road1 {
+                    continue;
+                }
+                // The carriageways run alongside each other, usually in opposite directions
+                let road_angle2 =
+                    angle_of_pt_on_line(&self.derived_ways[&road2].linestring, node2.pt);
+                let rotation = shortest_rotation(road_angle1, road_angle2).abs();
+                if rotation > 30.0 && rotation < 150.0 {
+                    continue;
+                }
+                // And the crossings are across from each other
+                let across = angle_of_line(Line::new(node1.pt, node2.pt));
+                if (shortest_rotation(across, road_angle1).abs() - 90.0).abs() > 30.0 {
+                    continue;
+                }
+                candidates.push((
+                    Euclidean.distance(*obj1.geom(), *obj2.geom()),
+                    obj1.data,
+                    obj2.data,
+                ));
             }
+        }
 
-            let mut new_tags = Tags::empty();
-            new_tags.insert("highway", "footway");
-            new_tags.insert("footway", "crossing");
-            // Store OSM reference: use crossing node ID (primary) and road way ID (always available as fallback)
-            new_tags.insert("tmp:osm_node_id", format!("node/{}", crossing_node_id.0));
-            new_tags.insert("tmp:osm_way_id", format!("way/{}", road_way_id.0));
-            // Copy one tag from the crossing node to the new crossing way
-            if let Some(value) = crossing_node.tags.get("crossing") {
-                new_tags.insert("crossing", value);
+        // Pair up the closest crossings first
+        candidates.sort_by(|a, b| a.0.total_cmp(&b.0).then((a.1, a.2).cmp(&(b.1, b.2))));
+        let mut paired = HashSet::new();
+        let mut pairs = Vec::new();
+        for (_, id1, id2) in candidates {
+            if !paired.contains(&id1) && !paired.contains(&id2) {
+                paired.insert(id1);
+                paired.insert(id2);
+                pairs.push((id1, id2));
             }
+        }
 
-            new_crossings.push((LineString::new(vec![endpt1, crossing_pt, endpt2]), new_tags));
+        let remaining = crossings
+            .into_iter()
+            .filter(|n| !paired.contains(n))
+            .collect();
+        (pairs, remaining)
+    }
+}
 
-            insert_new_nodes
-                .entry(sidewalk1)
-                .or_insert_with(Vec::new)
-                .push((endpt1, Tags::empty()));
-            insert_new_nodes
-                .entry(sidewalk2)
-                .or_insert_with(Vec::new)
-                .push((endpt2, Tags::empty()));
+pub(crate) fn crossing_way_tags(crossing_node: &Node, options: &CrossingOptions) -> Tags {
+    let mut tags = Tags::empty();
+    tags.insert("highway", "footway");
+    tags.insert("footway", "crossing");
+    for key in &options.transfer_tags {
+        if let Some(value) = crossing_node.tags.get(key) {
+            tags.insert(key, value);
         }
-        info!("Successfully made {} crossings", new_crossings.len());
+    }
 
-        CreateNewGeometry {
-            new_ways: new_crossings,
-            new_kind: Kind::Crossing,
-            insert_new_nodes,
-            modify_existing_way_tags: HashMap::new(),
+    if options.upgrade_legacy_values
+        && let Some(value) = crossing_node.tags.get("crossing")
+        && let Some((new_value, implied)) = upgrade_legacy_crossing(value)
+    {
+        match new_value {
+            Some(new_value) => tags.insert("crossing", new_value),
+            None => {
+                tags.remove("crossing");
+            }
+        }
+        // Anything tagged explicitly on the node wins
+        for (k, v) in implied {
+            if !crossing_node.tags.has(k) {
+                tags.insert(*k, *v);
+            }
         }
     }
+    tags
+}
+
+/// Is a crossing node tagged with a traffic island, including the legacy `crossing=island`?
+fn has_island(tags: &Tags) -> bool {
+    tags.is("crossing:island", "yes") || tags.is("crossing", "island")
+}
+
+fn traffic_island_tags() -> Tags {
+    let mut tags = Tags::empty();
+    tags.insert("highway", "footway");
+    tags.insert("footway", "traffic_island");
+    tags
+}
+
+/// Moves from `pt` toward `target`, going at most halfway.
+fn toward(pt: Coord, target: Coord, distance: f64) -> Coord {
+    let (pt, target) = (Point::from(pt), Point::from(target));
+    let distance = distance.min(Euclidean.distance(pt, target) / 2.0);
+    Euclidean
+        .point_at_distance_between(pt, target, distance)
+        .into()
 }
 
 fn aabb_line(line: &Line) -> AABB<Point> {
@@ -148,47 +714,48 @@ fn find_sidewalk_hit(
     closest_building: &RTree<Polygon>,
     crossing_node: &Node,
     angle: f64,
-) -> Option<(WayID, Coord)> {
+    side: Side,
+    probe: &Probe,
+) -> Result<(WayID, Coord), CrossingOutcome> {
     let crossing_pt = crossing_node.pt;
 
-    // First try to project a perpendicular line from the crossing out 10m (far away), and find the
-    // first sidewalk we hit.
-    let line1 = Line::new(crossing_pt, project_away(crossing_pt, angle, 10.0));
-
-    let mut candidates = Vec::new();
-    for obj in closest_sidewalk.locate_in_envelope_intersecting(&aabb_line(&line1)) {
-        for line2 in obj.geom().lines() {
-            if let Some(LineIntersection::SinglePoint { intersection, .. }) =
-                line_intersection(line1, line2)
-            {
-                candidates.push((obj.data, intersection));
-            }
-        }
-    }
-    // There could be multiple hits. Pick the one closest to the specified crossing_pt
-    if let Some(pair) = candidates.into_iter().min_by_key(|(_, end_pt)| {
-        to_cm(Euclidean.distance(Point::from(crossing_pt), Point::from(*end_pt)))
-    }) {
+    // First try to project a perpendicular line from the crossing out (10m by default, far away),
+    // and find the first sidewalk we hit.
+    if let Some(pair) = first_sidewalk_hit(closest_sidewalk, crossing_pt, angle, probe.distance) {
         // This branch already picks the closest hit and doesn't search as far. It's unlikely we
         // need to check if a road in closest_line hits.
-        return Some(pair);
+        return Ok(pair);
     }
 
     // If the perpendicular line didn't hit anything, try a second strategy. Project a point just a
-    // little bit (5m) to one side of the crossing, then find the closest point on any sidewalk to
-    // that.
+    // little bit (5m by default) to one side of the crossing, then find the closest point on any
+    // sidewalk within the probe distance (10m by default) of that.
     //
     // (It's tempting to just use this strategy always, instead of first trying perpendicular
     // lines. But when two sidewalks meet at a corner, sometimes we incorrectly pick one of them
     // based on how much we project away from the crossing_pt.)
-    let one_side_pt = project_away(crossing_pt, angle, 5.0);
-    let obj = closest_sidewalk.nearest_neighbor(&Point::from(one_side_pt))?;
+    let one_side_pt = project_away(crossing_pt, angle, probe.side_offset);
+    let obj = closest_sidewalk
+        .nearest_neighbor_iter_with_distance_2(&Point::from(one_side_pt))
+        .next()
+        .filter(|(_, dist_2)| *dist_2 <= probe.distance.powi(2))
+        .map(|(obj, _)| obj)
+        .ok_or(CrossingOutcome::NoSidewalk(side))?;
     // Then find the straight line to the crossing_pt using that matching sidewalk. Don't find the
     // closest point to one_side_pt, because that'll make a slightly angled crossing.
-    let (hit_way, endpt) = match obj.geom().closest_point(&Point::from(crossing_pt)) {
-        Closest::Intersection(pt) | Closest::SinglePoint(pt) => Some((obj.data, pt.into())),
-        Closest::Indeterminate => None,
+    let (hit_way, endpt): (WayID, Coord) = match obj.geom().closest_point(&Point::from(crossing_pt))
+    {
+        Closest::Intersection(pt) | Closest::SinglePoint(pt) => Ok((obj.data, pt.into())),
+        Closest::Indeterminate => Err(CrossingOutcome::NoSidewalk(side)),
     }?;
+    if Euclidean.distance(crossing_pt, endpt) > probe.max_snap_distance {
+        return Err(CrossingOutcome::NoSidewalk(side));
+    }
+    // The sidewalk across the road may be the closest one, but it's not on this side
+    let (dx, dy) = (endpt.x - crossing_pt.x, endpt.y - crossing_pt.y);
+    if dx * angle.to_radians().cos() + dy * angle.to_radians().sin() <= 0.0 {
+        return Err(CrossingOutcome::NoSidewalk(side));
+    }
 
     // We potentially found an endpt very far away. Make sure this line isn't crossing any existing
     // roads or other ways.
@@ -200,7 +767,7 @@ fn find_sidewalk_hit(
             && !crossing_node.way_ids.contains(&obj.data)
             && obj.geom().intersects(&test_line)
         {
-            return None;
+            return Err(CrossingOutcome::BlockedByRoad(side));
         }
     }
 
@@ -209,10 +776,35 @@ fn find_sidewalk_hit(
         .locate_in_envelope_intersecting(&aabb_line(&test_line))
         .any(|polygon| polygon.intersects(&test_line))
     {
-        return None;
+        return Err(CrossingOutcome::BlockedByBuilding(side));
     }
 
-    Some((hit_way, endpt))
+    Ok((hit_way, endpt))
+}
+
+/// Projects a line from `pt` and returns the closest place it hits a sidewalk.
+pub(crate) fn first_sidewalk_hit(
+    closest_sidewalk: &RTree<GeomWithData<LineString, WayID>>,
+    pt: Coord,
+    angle: f64,
+    distance: f64,
+) -> Option<(WayID, Coord)> {
+    let line1 = Line::new(pt, project_away(pt, angle, distance));
+
+    let mut candidates = Vec::new();
+    for obj in closest_sidewalk.locate_in_envelope_intersecting(&aabb_line(&line1)) {
+        for line2 in obj.geom().lines() {
+            if let Some(LineIntersection::SinglePoint { intersection, .. }) =
+                line_intersection(line1, line2)
+            {
+                candidates.push((obj.data, intersection));
+            }
+        }
+    }
+    // There could be multiple hits. Pick the one closest to pt
+    candidates
+        .into_iter()
+        .min_by_key(|(_, end_pt)| to_cm(Euclidean.distance(Point::from(pt), Point::from(*end_pt))))
 }
 
 // TODO Use new geo euclidean destination
@@ -225,7 +817,7 @@ fn project_away(pt: Coord, angle_degrees: f64, distance: f64) -> Coord {
 }
 
 // TODO Move more of these to utils
-fn angle_of_pt_on_line(linestring: &LineString, pt: Coord) -> f64 {
+pub(crate) fn angle_of_pt_on_line(linestring: &LineString, pt: Coord) -> f64 {
     let line = linestring
         .lines()
         .min_by_key(|line| (Euclidean.distance(line, pt) * 10e9) as usize)
@@ -233,7 +825,7 @@ fn angle_of_pt_on_line(linestring: &LineString, pt: Coord) -> f64 {
     angle_of_line(line)
 }
 
-fn angle_of_line(line: Line) -> f64 {
+pub(crate) fn angle_of_line(line: Line) -> f64 {
     line.dy().atan2(line.dx()).to_degrees()
 }
 
@@ -255,3 +847,371 @@ fn nearly_parallel(ls1: &LineString, ls2: &LineString, epsilon_degrees: f64) ->
 fn to_cm(x: f64) -> usize {
     (x * 100.0).round() as usize
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_ways(osm: &str) -> Vec<(LineString, Tags, Provenance)> {
+        let model = Speedwalk::new_from_osm(osm.as_bytes(), None).unwrap();
+        model
+            .connect_all_crossings(false, &CrossingOptions::default())
+            .new_ways
+    }
+
+    fn footway_values(new_ways: &[(LineString, Tags, Provenance)]) -> Vec<&str> {
+        new_ways
+            .iter()
+            .map(|(_, tags, _)| tags.get("footway").unwrap().as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_crossing_way_tags() {
+        let node = |pairs: Vec<&str>| Node {
+            pt: Coord { x: 0.0, y: 0.0 },
+            tags: Tags::new_from_pairs(&pairs),
+            version: 0,
+            way_ids: Vec::new(),
+            modified: false,
+            problems: Vec::new(),
+        };
+        let options = CrossingOptions::default();
+
+        let tags = crossing_way_tags(
+            &node(vec![
+                "highway=crossing",
+                "crossing=zebra",
+                "tactile_paving=yes",
+                "kerb=lowered",
+                "name=not copied",
+            ]),
+            &options,
+        );
+        assert_eq!(tags.get("crossing").unwrap(), "uncontrolled");
+        assert_eq!(tags.get("crossing:markings").unwrap(), "zebra");
+        assert_eq!(tags.get("tactile_paving").unwrap(), "yes");
+        assert_eq!(tags.get("kerb").unwrap(), "lowered");
+        assert!(!tags.has("name"));
+
+        // Explicit tags on the node win over implied ones
+        let tags = crossing_way_tags(
+            &node(vec!["crossing=zebra", "crossing:markings=dashes"]),
+            &options,
+        );
+        assert_eq!(tags.get("crossing:markings").unwrap(), "dashes");
+
+        let tags = crossing_way_tags(
+            &node(vec!["crossing=zebra"]),
+            &CrossingOptions {
+                transfer_tags: vec!["crossing".to_string()],
+                upgrade_legacy_values: false,
+                ..Default::default()
+            },
+        );
+        assert_eq!(tags.get("crossing").unwrap(), "zebra");
+        assert!(!tags.has("crossing:markings"));
+    }
+
+    #[test]
+    fn test_crossing_outcomes() {
+        // The sidewalk is only mapped on one side. Node 2 is a crossing over a driveway.
+        let osm = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <node id="1" lon="0.000000" lat="0.000000" version="1" />
+  <node id="2" lon="0.000300" lat="0.000000" version="1">
+    <tag k="highway" v="crossing"/>
+  </node>
+  <node id="3" lon="0.000600" lat="0.000000" version="1">
+    <tag k="highway" v="crossing"/>
+  </node>
+  <node id="4" lon="0.000900" lat="0.000000" version="1" />
+  <node id="5" lon="0.000000" lat="0.000040" version="1" />
+  <node id="6" lon="0.000900" lat="0.000040" version="1" />
+  <node id="7" lon="0.000300" lat="-0.000300" version="1" />
+  <way id="100" version="1">
+    <nd ref="1"/><nd ref="2"/><nd ref="3"/><nd ref="4"/>
+    <tag k="highway" v="secondary"/>
+    <tag k="sidewalk:both" v="separate"/>
+  </way>
+  <way id="101" version="1">
+    <nd ref="5"/><nd ref="6"/>
+    <tag k="highway" v="footway"/>
+    <tag k="footway" v="sidewalk"/>
+  </way>
+  <way id="102" version="1">
+    <nd ref="2"/><nd ref="7"/>
+    <tag k="highway" v="service"/>
+    <tag k="service" v="driveway"/>
+  </way>
+</osm>"#;
+        let model = Speedwalk::new_from_osm(osm.as_bytes(), None).unwrap();
+        let mut outcomes = model.crossing_outcomes(false, &CrossingOptions::default());
+        outcomes.sort_by_key(|(id, _)| *id);
+        assert_eq!(
+            outcomes,
+            vec![
+                (NodeID(2), CrossingOutcome::Driveway),
+                (NodeID(3), CrossingOutcome::NoSidewalk(Side::Right))
+            ]
+        );
+        assert_eq!(
+            model.derived_nodes[&NodeID(3)].problems[0].note,
+            "no sidewalk to connect crossing to (on the right)"
+        );
+    }
+
+    #[test]
+    fn test_road_width_aware_probe() {
+        // Sidewalks about 13m from the center of a 6-lane road
+        let osm = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <node id="1" lon="0.000000" lat="0.000000" version="1" />
+  <node id="2" lon="0.000450" lat="0.000000" version="1">
+    <tag k="highway" v="crossing"/>
+    <tag k="crossing" v="uncontrolled"/>
+  </node>
+  <node id="3" lon="0.000900" lat="0.000000" version="1" />
+  <node id="4" lon="0.000000" lat="0.000120" version="1" />
+  <node id="5" lon="0.000900" lat="0.000120" version="1" />
+  <node id="6" lon="0.000000" lat="-0.000120" version="1" />
+  <node id="7" lon="0.000900" lat="-0.000120" version="1" />
+  <way id="100" version="1">
+    <nd ref="1"/><nd ref="2"/><nd ref="3"/>
+    <tag k="highway" v="primary"/>
+    <tag k="lanes" v="6"/>
+    <tag k="sidewalk:both" v="separate"/>
+  </way>
+  <way id="101" version="1">
+    <nd ref="4"/><nd ref="5"/>
+    <tag k="highway" v="footway"/>
+    <tag k="footway" v="sidewalk"/>
+  </way>
+  <way id="102" version="1">
+    <nd ref="6"/><nd ref="7"/>
+    <tag k="highway" v="footway"/>
+    <tag k="footway" v="sidewalk"/>
+  </way>
+</osm>"#;
+        let model = Speedwalk::new_from_osm(osm.as_bytes(), None).unwrap();
+        let mut options = CrossingOptions {
+            max_snap_distance_meters: 12.0,
+            ..Default::default()
+        };
+        assert_eq!(
+            model.crossing_outcomes(false, &options),
+            vec![(NodeID(2), CrossingOutcome::Connected)]
+        );
+
+        options.road_width_aware = false;
+        assert!(matches!(
+            model.crossing_outcomes(false, &options)[0].1,
+            CrossingOutcome::NoSidewalk(_)
+        ));
+    }
+
+    #[test]
+    fn test_problems_use_last_crossing_options() {
+        // Sidewalks about 4m either side of the road
+        let osm = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <node id="1" lon="0.000000" lat="0.000000" version="1" />
+  <node id="2" lon="0.000450" lat="0.000000" version="1">
+    <tag k="highway" v="crossing"/>
+    <tag k="crossing" v="uncontrolled"/>
+  </node>
+  <node id="3" lon="0.000900" lat="0.000000" version="1" />
+  <node id="4" lon="0.000000" lat="0.000040" version="1" />
+  <node id="5" lon="0.000900" lat="0.000040" version="1" />
+  <node id="6" lon="0.000000" lat="-0.000040" version="1" />
+  <node id="7" lon="0.000900" lat="-0.000040" version="1" />
+  <way id="100" version="1">
+    <nd ref="1"/><nd ref="2"/><nd ref="3"/>
+    <tag k="highway" v="secondary"/>
+    <tag k="sidewalk:both" v="separate"/>
+  </way>
+  <way id="101" version="1">
+    <nd ref="4"/><nd ref="5"/>
+    <tag k="highway" v="footway"/>
+    <tag k="footway" v="sidewalk"/>
+  </way>
+  <way id="102" version="1">
+    <nd ref="6"/><nd ref="7"/>
+    <tag k="highway" v="footway"/>
+    <tag k="footway" v="sidewalk"/>
+  </way>
+</osm>"#;
+        let mut model = Speedwalk::new_from_osm(osm.as_bytes(), None).unwrap();
+        let has_problem = |model: &Speedwalk| {
+            model.derived_nodes[&NodeID(2)]
+                .problems
+                .iter()
+                .any(|p| p.problem_type == ProblemType::CrossingNoSidewalk)
+        };
+        // The default distances would connect it
+        assert!(!has_problem(&model));
+
+        // After a run with shorter distances couldn't, the problem uses those distances too
+        let options = CrossingOptions {
+            probe_distance_meters: 2.0,
+            max_snap_distance_meters: 2.0,
+            ..Default::default()
+        };
+        let mut edits = model.take_edits();
+        edits
+            .apply_cmd(UserCmd::ConnectAllCrossings(false, options), &model)
+            .unwrap();
+        model.set_edits(edits);
+        model.after_edit();
+        assert!(has_problem(&model));
+    }
+
+    #[test]
+    fn test_road_width() {
+        let tags = |k: &str, v: &str| {
+            let mut tags = Tags::empty();
+            tags.insert(k, v);
+            tags
+        };
+        assert_eq!(road_width(&tags("width", "7.5 m")), Some(7.5));
+        assert_eq!(road_width(&tags("lanes", "2")), Some(6.0));
+        assert_eq!(road_width(&tags("highway", "primary")), None);
+    }
+
+    #[test]
+    fn test_island_tagged_on_node() {
+        let osm = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <node id="1" lon="0.000000" lat="0.000000" version="1" />
+  <node id="2" lon="0.000450" lat="0.000000" version="1">
+    <tag k="highway" v="crossing"/>
+    <tag k="crossing" v="uncontrolled"/>
+    <tag k="crossing:island" v="yes"/>
+  </node>
+  <node id="3" lon="0.000900" lat="0.000000" version="1" />
+  <node id="4" lon="0.000000" lat="0.000040" version="1" />
+  <node id="5" lon="0.000900" lat="0.000040" version="1" />
+  <node id="6" lon="0.000000" lat="-0.000040" version="1" />
+  <node id="7" lon="0.000900" lat="-0.000040" version="1" />
+  <way id="100" version="1">
+    <nd ref="1"/><nd ref="2"/><nd ref="3"/>
+    <tag k="highway" v="secondary"/>
+    <tag k="sidewalk:both" v="separate"/>
+  </way>
+  <way id="101" version="1">
+    <nd ref="4"/><nd ref="5"/>
+    <tag k="highway" v="footway"/>
+    <tag k="footway" v="sidewalk"/>
+  </way>
+  <way id="102" version="1">
+    <nd ref="6"/><nd ref="7"/>
+    <tag k="highway" v="footway"/>
+    <tag k="footway" v="sidewalk"/>
+  </way>
+</osm>"#;
+        let new_ways = new_ways(osm);
+        assert_eq!(
+            footway_values(&new_ways),
+            vec!["crossing", "traffic_island", "crossing"]
+        );
+        assert_eq!(new_ways[0].1.get("crossing").unwrap(), "uncontrolled");
+        // The crossing node is on a crossing segment, not the island
+        assert_eq!(new_ways[0].0.0.len(), 3);
+        assert_eq!(new_ways[1].0.0.len(), 2);
+
+        // Applying it leaves no crossing node on a footway that isn't a crossing
+        let mut model = Speedwalk::new_from_osm(osm.as_bytes(), None).unwrap();
+        let mut edits = model.take_edits();
+        edits
+            .apply_cmd(
+                crate::UserCmd::ConnectAllCrossings(false, CrossingOptions::default()),
+                &model,
+            )
+            .unwrap();
+        model.set_edits(edits);
+        model.after_edit();
+        let node = &model.derived_nodes[&NodeID(2)];
+        assert!(
+            node.way_ids
+                .iter()
+                .any(|w| model.derived_ways[w].tags.is("footway", "crossing"))
+        );
+        assert!(model.derived_ways.values().all(|way| {
+            way.problems
+                .iter()
+                .all(|p| p.problem_type != ProblemType::MissingFootwayCrossing)
+        }));
+
+        // The legacy tag means the same
+        let legacy = osm.replace(
+            r#"<tag k="crossing" v="uncontrolled"/>
+    <tag k="crossing:island" v="yes"/>"#,
+            r#"<tag k="crossing" v="island"/>"#,
+        );
+        let legacy_ways = Speedwalk::new_from_osm(legacy.as_bytes(), None)
+            .unwrap()
+            .connect_all_crossings(false, &CrossingOptions::default())
+            .new_ways;
+        assert_eq!(
+            footway_values(&legacy_ways),
+            vec!["crossing", "traffic_island", "crossing"]
+        );
+    }
+
+    #[test]
+    fn test_dual_carriageway_crossings() {
+        let osm = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <node id="1" lon="0.000000" lat="0.000050" version="1" />
+  <node id="2" lon="0.000450" lat="0.000050" version="1">
+    <tag k="highway" v="crossing"/>
+    <tag k="crossing" v="traffic_signals"/>
+  </node>
+  <node id="3" lon="0.000900" lat="0.000050" version="1" />
+  <node id="11" lon="0.000900" lat="-0.000050" version="1" />
+  <node id="12" lon="0.000450" lat="-0.000050" version="1">
+    <tag k="highway" v="crossing"/>
+    <tag k="crossing" v="traffic_signals"/>
+  </node>
+  <node id="13" lon="0.000000" lat="-0.000050" version="1" />
+  <node id="4" lon="0.000000" lat="0.000100" version="1" />
+  <node id="5" lon="0.000900" lat="0.000100" version="1" />
+  <node id="6" lon="0.000000" lat="-0.000100" version="1" />
+  <node id="7" lon="0.000900" lat="-0.000100" version="1" />
+  <way id="100" version="1">
+    <nd ref="1"/><nd ref="2"/><nd ref="3"/>
+    <tag k="highway" v="primary"/>
+    <tag k="oneway" v="yes"/>
+    <tag k="sidewalk:both" v="separate"/>
+  </way>
+  <way id="110" version="1">
+    <nd ref="13"/><nd ref="12"/><nd ref="11"/>
+    <tag k="highway" v="primary"/>
+    <tag k="oneway" v="-1"/>
+    <tag k="sidewalk:both" v="separate"/>
+  </way>
+  <way id="101" version="1">
+    <nd ref="4"/><nd ref="5"/>
+    <tag k="highway" v="footway"/>
+    <tag k="footway" v="sidewalk"/>
+  </way>
+  <way id="102" version="1">
+    <nd ref="6"/><nd ref="7"/>
+    <tag k="highway" v="footway"/>
+    <tag k="footway" v="sidewalk"/>
+  </way>
+</osm>"#;
+        let new_ways = new_ways(osm);
+        assert_eq!(
+            footway_values(&new_ways),
+            vec!["crossing", "traffic_island", "crossing"]
+        );
+        // Each carriageway's crossing node is on its own crossing segment
+        let nodes: Vec<NodeID> = new_ways
+            .iter()
+            .filter(|(_, tags, _)| tags.is("footway", "crossing"))
+            .map(|(_, _, provenance)| provenance.osm_node.unwrap())
+            .collect();
+        assert!(nodes.contains(&NodeID(2)) && nodes.contains(&NodeID(12)));
+    }
+}
@@ -0,0 +1,345 @@
+use geo::{
+    Closest, ClosestPoint, Distance, Euclidean, InterpolatableLine, Length, Line, LineString, Point,
+};
+use osm_reader::WayID;
+use rstar::{Envelope, RTree, primitives::GeomWithData};
+use utils::{LineSplit, aabb, buffer_aabb};
+
+use crate::{
+    Kind, Speedwalk, Way,
+    crossings::{angle_of_line, angle_of_pt_on_line, shortest_rotation},
+    problems::ProblemScope,
+};
+
+/// Sidewalks closer than this are drawn on top of each other
+const OVERLAP_BUFFER: f64 = 3.0;
+/// How much of the shorter sidewalk has to overlap the other
+const MIN_OVERLAP_RATIO: f64 = 0.5;
+/// Both directions count as the same bearing
+const MAX_ANGLE_DIFF: f64 = 20.0;
+/// How far apart to check points along the shorter sidewalk
+const SAMPLE_STEP: f64 = 1.0;
+/// A closest point this near an end of the other sidewalk is at that end
+const END_TOLERANCE: f64 = 0.1;
+
+pub(crate) struct DuplicateSidewalk {
+    pub sidewalk1: WayID,
+    pub sidewalk2: WayID,
+    /// The part of the shorter sidewalk overlapping the other
+    pub overlap: LineString,
+    /// The fraction of the shorter sidewalk overlapping
+    pub ratio: f64,
+}
+
+/// What to do about a pair of duplicate sidewalks
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum DuplicateFix {
+    /// Delete this generated sidewalk, keeping the existing one
+    Delete,
+    /// Keep this existing sidewalk and delete the generated one
+    Keep,
+    /// Neither is generated, so merge them
+    Merge,
+}
+
+impl DuplicateSidewalk {
+    /// What to do with `sidewalk`, one of the pair. Generated sidewalks haven't been uploaded yet,
+    /// so are cheaper to delete.
+    pub fn suggestion(&self, model: &Speedwalk, sidewalk: WayID) -> DuplicateFix {
+        let other = if sidewalk == self.sidewalk1 {
+            self.sidewalk2
+        } else {
+            self.sidewalk1
+        };
+        let generated = |id: WayID| model.derived_ways[&id].provenance.osm_way.is_some();
+        match (generated(sidewalk), generated(other)) {
+            (true, false) => DuplicateFix::Delete,
+            (false, true) => DuplicateFix::Keep,
+            _ => DuplicateFix::Merge,
+        }
+    }
+}
+
+impl Speedwalk {
+    /// Pairs of sidewalks drawn on top of each other, with at least one in scope. Sorted by the
+    /// pair of IDs.
+    pub(crate) fn find_duplicate_sidewalks(
+        &self,
+        scope: Option<&ProblemScope>,
+    ) -> Vec<DuplicateSidewalk> {
+        let mut sidewalks: Vec<WayID> = self
+            .derived_ways
+            .iter()
+            .filter(|(_, way)| way.kind == Kind::Sidewalk && scope.is_none_or(|s| s.has_way(way)))
+            .map(|(id, _)| *id)
+            .collect();
+        sidewalks.sort();
+        let Some(search) = sidewalks
+            .iter()
+            .map(|id| buffer_aabb(aabb(&self.derived_ways[id].linestring), OVERLAP_BUFFER))
+            .reduce(|a, b| a.merged(&b))
+        else {
+            return Vec::new();
+        };
+        let closest_sidewalk = RTree::bulk_load(
+            self.derived_ways
+                .iter()
+                .filter(|(_, way)| {
+                    way.kind == Kind::Sidewalk && search.intersects(&aabb(&way.linestring))
+                })
+                .map(|(id, way)| GeomWithData::new(way.linestring.clone(), *id))
+                .collect(),
+        );
+
+        let mut pairs = Vec::new();
+        for id in sidewalks {
+            let linestring = &self.derived_ways[&id].linestring;
+            for obj in closest_sidewalk
+                .locate_in_envelope_intersecting(&buffer_aabb(aabb(linestring), OVERLAP_BUFFER))
+            {
+                if obj.data != id {
+                    pairs.push((id.min(obj.data), id.max(obj.data)));
+                }
+            }
+        }
+        pairs.sort();
+        pairs.dedup();
+
+        let mut results = Vec::new();
+        for (sidewalk1, sidewalk2) in pairs {
+            let (way1, way2) = (
+                &self.derived_ways[&sidewalk1],
+                &self.derived_ways[&sidewalk2],
+            );
+            if only_touch_at_end(way1, way2) {
+                continue;
+            }
+            let (ls1, ls2) = (&way1.linestring, &way2.linestring);
+            let (shorter, longer) = if Euclidean.length(ls1) <= Euclidean.length(ls2) {
+                (ls1, ls2)
+            } else {
+                (ls2, ls1)
+            };
+            if let Some((overlap, ratio)) = find_overlap(shorter, longer) {
+                results.push(DuplicateSidewalk {
+                    sidewalk1,
+                    sidewalk2,
+                    overlap,
+                    ratio,
+                });
+            }
+        }
+        results
+    }
+}
+
+/// Checks points along `a` to see how much of it runs along `b`
+fn find_overlap(a: &LineString, b: &LineString) -> Option<(LineString, f64)> {
+    let length = Euclidean.length(a);
+    if length == 0.0 {
+        return None;
+    }
+    let steps = (length / SAMPLE_STEP).ceil() as usize;
+
+    let mut matches = 0;
+    let mut first = None;
+    let mut last = 0.0;
+    for i in 0..=steps {
+        let fraction = (i as f64) / (steps as f64);
+        let pt: Point = a.point_at_ratio_from_start(&Euclidean, fraction)?;
+        // Near the end of `b` doesn't count, or a sidewalk continuing from it would
+        let Some(on_b) = closest_interior_point(b, pt) else {
+            continue;
+        };
+        if Euclidean.distance(on_b, pt) > OVERLAP_BUFFER {
+            continue;
+        }
+        let diff = shortest_rotation(
+            angle_of_pt_on_line(a, pt.into()),
+            angle_of_pt_on_line(b, pt.into()),
+        )
+        .abs();
+        if diff.min(180.0 - diff) > MAX_ANGLE_DIFF {
+            continue;
+        }
+        matches += 1;
+        first.get_or_insert(fraction);
+        last = fraction;
+    }
+
+    let ratio = (matches as f64) / ((steps + 1) as f64);
+    if ratio < MIN_OVERLAP_RATIO {
+        return None;
+    }
+    let overlap = a.line_split_twice(first?, last)?.into_second()?;
+    Some((overlap, ratio))
+}
+
+/// The closest point on `ls` to `pt`, unless that's one of its ends
+fn closest_interior_point(ls: &LineString, pt: Point) -> Option<Point> {
+    let closest = match ls.closest_point(&pt) {
+        Closest::Intersection(x) | Closest::SinglePoint(x) => x,
+        Closest::Indeterminate => return None,
+    };
+    let ends = [Point::from(*ls.0.first()?), Point::from(*ls.0.last()?)];
+    if ends
+        .iter()
+        .any(|end| Euclidean.distance(*end, closest) < END_TOLERANCE)
+    {
+        return None;
+    }
+    Some(closest)
+}
+
+/// True if the ways only share one node, at an end of both, and leave it in different
+/// directions. One continues from the other, like a sidewalk split at a crossing.
+fn only_touch_at_end(way1: &Way, way2: &Way) -> bool {
+    let shared: Vec<_> = way1
+        .node_ids
+        .iter()
+        .filter(|n| way2.node_ids.contains(n))
+        .collect();
+    if shared.len() != 1 {
+        return false;
+    }
+    let (Some(angle1), Some(angle2)) = (
+        angle_leaving_end(way1, *shared[0]),
+        angle_leaving_end(way2, *shared[0]),
+    ) else {
+        return false;
+    };
+    shortest_rotation(angle1, angle2).abs() > 90.0
+}
+
+/// The angle of the way's first segment leaving `node`, if it's an end of the way
+fn angle_leaving_end(way: &Way, node: osm_reader::NodeID) -> Option<f64> {
+    let pts = &way.linestring.0;
+    if pts.len() < 2 {
+        return None;
+    }
+    if way.node_ids.first() == Some(&node) {
+        Some(angle_of_line(Line::new(pts[0], pts[1])))
+    } else if way.node_ids.last() == Some(&node) {
+        let n = pts.len();
+        Some(angle_of_line(Line::new(pts[n - 1], pts[n - 2])))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duplicate_sidewalks() {
+        // 200 and 201 are drawn on top of each other, 202 is further away, and 203 is
+        // perpendicular
+        let osm = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <node id="1" lon="0.000000" lat="0.000000" version="1" />
+  <node id="2" lon="0.000900" lat="0.000000" version="1" />
+  <node id="3" lon="0.000100" lat="0.000010" version="1" />
+  <node id="4" lon="0.000800" lat="0.000010" version="1" />
+  <node id="5" lon="0.000000" lat="0.000200" version="1" />
+  <node id="6" lon="0.000900" lat="0.000200" version="1" />
+  <node id="7" lon="0.000450" lat="-0.000100" version="1" />
+  <node id="8" lon="0.000450" lat="0.000100" version="1" />
+  <way id="200" version="1">
+    <nd ref="1"/><nd ref="2"/>
+    <tag k="highway" v="footway"/>
+    <tag k="footway" v="sidewalk"/>
+  </way>
+  <way id="201" version="1">
+    <nd ref="4"/><nd ref="3"/>
+    <tag k="highway" v="footway"/>
+    <tag k="footway" v="sidewalk"/>
+  </way>
+  <way id="202" version="1">
+    <nd ref="5"/><nd ref="6"/>
+    <tag k="highway" v="footway"/>
+    <tag k="footway" v="sidewalk"/>
+  </way>
+  <way id="203" version="1">
+    <nd ref="7"/><nd ref="8"/>
+    <tag k="highway" v="footway"/>
+    <tag k="footway" v="sidewalk"/>
+  </way>
+</osm>"#;
+        let mut model = Speedwalk::new_from_osm(osm.as_bytes(), None).unwrap();
+        let found = model.find_duplicate_sidewalks(None);
+        assert_eq!(found.len(), 1);
+        assert_eq!(
+            (found[0].sidewalk1, found[0].sidewalk2),
+            (WayID(200), WayID(201))
+        );
+        assert!(found[0].ratio > 0.9);
+        assert_eq!(found[0].suggestion(&model, WayID(200)), DuplicateFix::Merge);
+
+        for id in [200, 201] {
+            assert!(
+                model.derived_ways[&WayID(id)]
+                    .problems
+                    .iter()
+                    .any(|p| p.problem_type == crate::ProblemType::DuplicateSidewalk)
+            );
+        }
+        assert!(model.derived_ways[&WayID(202)].problems.is_empty());
+
+        // Pretend 201 was generated from a road
+        model
+            .derived_ways
+            .get_mut(&WayID(201))
+            .unwrap()
+            .provenance
+            .osm_way = Some(WayID(100));
+        assert_eq!(found[0].suggestion(&model, WayID(200)), DuplicateFix::Keep);
+        assert_eq!(
+            found[0].suggestion(&model, WayID(201)),
+            DuplicateFix::Delete
+        );
+    }
+
+    #[test]
+    fn test_continuing_sidewalks_arent_duplicates() {
+        // 201 is a 5m stub continuing straight from 200 at node 2. 203 does the same from 202,
+        // but without a shared node.
+        let osm = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <node id="1" lon="0.000000" lat="0.000000" version="1" />
+  <node id="2" lon="0.000450" lat="0.000000" version="1" />
+  <node id="3" lon="0.000495" lat="0.000000" version="1" />
+  <node id="4" lon="0.000000" lat="0.001000" version="1" />
+  <node id="5" lon="0.000450" lat="0.001000" version="1" />
+  <node id="6" lon="0.000451" lat="0.001000" version="1" />
+  <node id="7" lon="0.000496" lat="0.001000" version="1" />
+  <way id="200" version="1">
+    <nd ref="1"/><nd ref="2"/>
+    <tag k="highway" v="footway"/>
+    <tag k="footway" v="sidewalk"/>
+  </way>
+  <way id="201" version="1">
+    <nd ref="2"/><nd ref="3"/>
+    <tag k="highway" v="footway"/>
+    <tag k="footway" v="sidewalk"/>
+  </way>
+  <way id="202" version="1">
+    <nd ref="4"/><nd ref="5"/>
+    <tag k="highway" v="footway"/>
+    <tag k="footway" v="sidewalk"/>
+  </way>
+  <way id="203" version="1">
+    <nd ref="6"/><nd ref="7"/>
+    <tag k="highway" v="footway"/>
+    <tag k="footway" v="sidewalk"/>
+  </way>
+</osm>"#;
+        let model = Speedwalk::new_from_osm(osm.as_bytes(), None).unwrap();
+        assert!(model.find_duplicate_sidewalks(None).is_empty());
+        assert!(model.derived_ways.values().all(|way| {
+            way.problems
+                .iter()
+                .all(|p| p.problem_type != crate::ProblemType::DuplicateSidewalk)
+        }));
+    }
+}
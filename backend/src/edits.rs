@@ -8,12 +8,12 @@ use serde::Serialize;
 use utils::Tags;
 
 use crate::{
-    Kind, Node, Speedwalk, Way,
+    Kind, Node, Provenance, Speedwalk, Way, audit,
+    crossings::CrossingOptions,
     graph::{Edge, Graph},
+    make_sidewalks::SidewalkOptions,
 };
 
-const MAX_CROSSING_SNAP_DISTANCE_METERS: f64 = 25.0;
-
 struct SnappedCrossingSegment {
     start_way: WayID,
     end_way: WayID,
@@ -34,6 +34,7 @@ fn snap_crossing_segment_with_way_ids(
     model: &Speedwalk,
     start_wgs84: Point,
     end_wgs84: Point,
+    max_snap_distance: f64,
 ) -> Result<SnappedCrossingSegment> {
     let start_pt = model.mercator.to_mercator(&start_wgs84);
     let end_pt = model.mercator.to_mercator(&end_wgs84);
@@ -55,11 +56,11 @@ fn snap_crossing_segment_with_way_ids(
         };
         let snapped_wgs84 = Point::from(model.mercator.pt_to_wgs84(snapped));
         let dist_m = Haversine.distance(pt_wgs84, snapped_wgs84);
-        if dist_m > MAX_CROSSING_SNAP_DISTANCE_METERS {
+        if dist_m > max_snap_distance {
             bail!(
                 "Point is {:.1}m from the nearest routable line (max {:.0}m). Move it closer to the intended road/footway.",
                 dist_m,
-                MAX_CROSSING_SNAP_DISTANCE_METERS
+                max_snap_distance
             );
         }
         Ok((obj.data, snapped))
@@ -82,7 +83,12 @@ fn snap_crossing_segment_with_way_ids(
     })
 }
 
-fn snap_point_to_way(model: &Speedwalk, way_id: WayID, pt_wgs84: Point) -> Result<Coord> {
+fn snap_point_to_way(
+    model: &Speedwalk,
+    way_id: WayID,
+    pt_wgs84: Point,
+    max_snap_distance: f64,
+) -> Result<Coord> {
     let pt_mercator = model.mercator.to_mercator(&pt_wgs84);
     let way = model
         .derived_ways
@@ -97,11 +103,11 @@ fn snap_point_to_way(model: &Speedwalk, way_id: WayID, pt_wgs84: Point) -> Resul
     };
     let snapped_wgs84 = Point::from(model.mercator.pt_to_wgs84(snapped));
     let dist_m = Haversine.distance(pt_wgs84, snapped_wgs84);
-    if dist_m > MAX_CROSSING_SNAP_DISTANCE_METERS {
+    if dist_m > max_snap_distance {
         bail!(
             "Point is {:.1}m from the selected way (max {:.0}m). Move it closer to the intended road/path.",
             dist_m,
-            MAX_CROSSING_SNAP_DISTANCE_METERS
+            max_snap_distance
         );
     }
     Ok(snapped)
@@ -112,8 +118,10 @@ pub fn resolve_crossing_segment(
     model: &Speedwalk,
     start_wgs84: Point,
     end_wgs84: Point,
+    max_snap_distance: f64,
 ) -> Result<ResolvedCrossingSegment> {
-    let snapped = snap_crossing_segment_with_way_ids(model, start_wgs84, end_wgs84)?;
+    let snapped =
+        snap_crossing_segment_with_way_ids(model, start_wgs84, end_wgs84, max_snap_distance)?;
     let start_out = model.mercator.pt_to_wgs84(snapped.snapped_start);
     let end_out = model.mercator.pt_to_wgs84(snapped.snapped_end);
     Ok(ResolvedCrossingSegment {
@@ -131,8 +139,9 @@ fn snap_crossing_segment(
     model: &Speedwalk,
     start_wgs84: Point,
     end_wgs84: Point,
+    max_snap_distance: f64,
 ) -> Result<(Point, Point)> {
-    let resolved = resolve_crossing_segment(model, start_wgs84, end_wgs84)?;
+    let resolved = resolve_crossing_segment(model, start_wgs84, end_wgs84, max_snap_distance)?;
     Ok((
         Point::new(resolved.start_lng, resolved.start_lat),
         Point::new(resolved.end_lng, resolved.end_lat),
@@ -192,15 +201,17 @@ pub fn resolve_manual_deletion_edges(
     model: &Speedwalk,
     start_wgs84: Point,
     end_wgs84: Point,
+    max_snap_distance: f64,
 ) -> Result<Vec<ResolvedDeletionEdge>> {
-    let snapped = snap_crossing_segment_with_way_ids(model, start_wgs84, end_wgs84)?;
+    let snapped =
+        snap_crossing_segment_with_way_ids(model, start_wgs84, end_wgs84, max_snap_distance)?;
     // Deletions are always resolved on one way. If B snaps to a different way than A, keep A's way
     // and project B onto that same way instead of failing outright.
     let way_id = snapped.start_way;
     let snapped_end = if snapped.start_way == snapped.end_way {
         snapped.snapped_end
     } else {
-        snap_point_to_way(model, way_id, end_wgs84)?
+        snap_point_to_way(model, way_id, end_wgs84, max_snap_distance)?
     };
     let way = &model.derived_ways[&way_id];
     let ls = &way.linestring;
@@ -272,6 +283,7 @@ pub struct Edits {
     // TODO Or maybe ditch TagCmd and the equivalent for inserting nodes somewhere
     change_way_tags: HashMap<WayID, Vec<TagCmd>>,
     change_way_nodes: HashMap<WayID, Vec<NodeID>>,
+    change_node_tags: HashMap<NodeID, Vec<TagCmd>>,
 
     new_nodes: HashMap<NodeID, Node>,
     new_ways: HashMap<WayID, Way>,
@@ -286,12 +298,23 @@ pub enum UserCmd {
         remove_keys: Vec<String>,
         add_tags: Vec<(String, String)>,
     },
-    MakeAllSidewalks(bool),
-    ConnectAllCrossings(bool),
+    SetNodeTags {
+        node: NodeID,
+        remove_keys: Vec<String>,
+        add_tags: Vec<(String, String)>,
+    },
+    MakeAllSidewalks(bool, SidewalkOptions),
+    /// Only some of the sidewalks `MakeAllSidewalks` would create, by index into its results
+    MakeSelectedSidewalks(bool, SidewalkOptions, Vec<usize>),
+    ConnectAllCrossings(bool, CrossingOptions),
+    /// Create crossing ways at junction corners where arms have no crossing, tagged with this
+    /// `crossing` value
+    ConnectJunctionCorners(audit::Options, String),
     AssumeTags(bool),
     AddCrossings(Vec<Point>, Tags),
-    /// Add a crossing as a segment between two points; each point is snapped to the nearest road or sidewalk (closest line).
-    AddCrossingSegment(Point, Point, Tags),
+    /// Add a crossing as a segment between two points; each point is snapped to the nearest road or sidewalk (closest line)
+    /// within the max snap distance in meters.
+    AddCrossingSegment(Point, Point, Tags, f64),
     /// Add a crossing from previously resolved snapped points and target ways.
     AddCrossingSegmentSnapped {
         start_way: WayID,
@@ -306,6 +329,10 @@ pub enum UserCmd {
         node1: NodeID,
         node2: NodeID,
     },
+    /// Insert an untagged node shared with every road this footway crosses without one
+    SplitFootwayAtRoads(WayID),
+    /// Extend dangling ends of this way to the walkable ways they nearly reach
+    SnapEndpoints(WayID),
 }
 
 pub enum TagCmd {
@@ -342,12 +369,44 @@ impl Edits {
                     cmds.push(TagCmd::Set(k, v));
                 }
             }
-            UserCmd::MakeAllSidewalks(only_severances) => {
-                let results = model.make_all_sidewalks(only_severances);
+            UserCmd::SetNodeTags {
+                node,
+                remove_keys,
+                add_tags,
+            } => {
+                // New nodes can be changed directly
+                if let Some(new_node) = self.new_nodes.get_mut(&node) {
+                    for key in remove_keys {
+                        new_node.tags.remove(&key);
+                    }
+                    for (k, v) in add_tags {
+                        new_node.tags.insert(k, v);
+                    }
+                    return Ok(());
+                }
+
+                let cmds = self.change_node_tags.entry(node).or_default();
+                for key in remove_keys {
+                    cmds.push(TagCmd::Remove(key));
+                }
+                for (k, v) in add_tags {
+                    cmds.push(TagCmd::Set(k, v));
+                }
+            }
+            UserCmd::MakeAllSidewalks(only_severances, options) => {
+                let results = model.make_all_sidewalks(only_severances, &options);
                 self.create_new_geometry(results, model);
             }
-            UserCmd::ConnectAllCrossings(include_crossing_no) => {
-                let results = model.connect_all_crossings(include_crossing_no);
+            UserCmd::MakeSelectedSidewalks(only_severances, options, selected) => {
+                let results = model.make_selected_sidewalks(only_severances, &options, &selected);
+                self.create_new_geometry(results, model);
+            }
+            UserCmd::ConnectAllCrossings(include_crossing_no, options) => {
+                let results = model.connect_all_crossings(include_crossing_no, &options);
+                self.create_new_geometry(results, model);
+            }
+            UserCmd::ConnectJunctionCorners(options, crossing_value) => {
+                let results = model.connect_junction_corners(&options, &crossing_value);
                 self.create_new_geometry(results, model);
             }
             UserCmd::AssumeTags(drive_on_left) => {
@@ -412,8 +471,13 @@ impl Edits {
                     model,
                 );
             }
-            UserCmd::AddCrossingSegment(start_wgs84, end_wgs84, way_tags) => {
-                let snapped = snap_crossing_segment_with_way_ids(model, start_wgs84, end_wgs84)?;
+            UserCmd::AddCrossingSegment(start_wgs84, end_wgs84, way_tags, max_snap_distance) => {
+                let snapped = snap_crossing_segment_with_way_ids(
+                    model,
+                    start_wgs84,
+                    end_wgs84,
+                    max_snap_distance,
+                )?;
                 let node_tags = way_tags.clone();
                 let mut insert_new_nodes: HashMap<WayID, Vec<(Coord, Tags)>> = HashMap::new();
                 insert_new_nodes
@@ -426,7 +490,7 @@ impl Edits {
                     .push((snapped.snapped_end, node_tags));
                 let crossing_way =
                     LineString::new(vec![snapped.snapped_start, snapped.snapped_end]);
-                let new_ways = vec![(crossing_way, way_tags)];
+                let new_ways = vec![(crossing_way, way_tags, Provenance::default())];
                 self.create_new_geometry(
                     CreateNewGeometry {
                         new_ways,
@@ -457,7 +521,7 @@ impl Edits {
                     .or_default()
                     .push((snapped_end.into(), node_tags));
                 let crossing_way = LineString::new(vec![snapped_start.into(), snapped_end.into()]);
-                let new_ways = vec![(crossing_way, way_tags)];
+                let new_ways = vec![(crossing_way, way_tags, Provenance::default())];
                 self.create_new_geometry(
                     CreateNewGeometry {
                         new_ways,
@@ -471,6 +535,84 @@ impl Edits {
             UserCmd::ManualDeleteEdge { way, node1, node2 } => {
                 self.manual_deleted_edges.insert((way, node1, node2));
             }
+            UserCmd::SplitFootwayAtRoads(footway) => {
+                let mut insert_nodes: HashMap<WayID, Vec<(NodeID, Coord)>> = HashMap::new();
+                for (road, pt) in model.unsplit_road_crossings_on(footway) {
+                    let node_id = self.new_node_id();
+                    // Untagged, since the footway isn't footway=crossing. Tagging the crossing is
+                    // left to the mapper.
+                    self.new_nodes.insert(
+                        node_id,
+                        Node {
+                            pt,
+                            tags: Tags::empty(),
+                            version: 0,
+
+                            // Calculate later
+                            way_ids: Vec::new(),
+                            modified: true,
+                            problems: Vec::new(),
+                        },
+                    );
+                    insert_nodes.entry(footway).or_default().push((node_id, pt));
+                    insert_nodes.entry(road).or_default().push((node_id, pt));
+                }
+                if insert_nodes.is_empty() {
+                    bail!("{footway} doesn't cross any roads without a shared node");
+                }
+                for (way_id, nodes) in insert_nodes {
+                    self.insert_nodes_into_way(model, way_id, nodes);
+                }
+            }
+            UserCmd::SnapEndpoints(way) => {
+                let near_misses = model.near_misses_on(way);
+                if near_misses.is_empty() {
+                    bail!("{way} doesn't have any dangling ends near another way");
+                }
+                let mut node_ids = model.derived_ways[&way].node_ids.clone();
+                let mut insert_nodes: HashMap<WayID, Vec<(NodeID, Coord)>> = HashMap::new();
+                for near_miss in near_misses {
+                    // Reuse a node already on the target way if it's right there
+                    let existing = model.derived_ways[&near_miss.target]
+                        .node_ids
+                        .iter()
+                        .find(|n| {
+                            Euclidean.distance(model.derived_nodes[*n].pt, near_miss.snapped) < 0.1
+                        })
+                        .cloned();
+                    let node_id = existing.unwrap_or_else(|| {
+                        let node_id = self.new_node_id();
+                        self.new_nodes.insert(
+                            node_id,
+                            Node {
+                                pt: near_miss.snapped,
+                                tags: Tags::empty(),
+                                version: 0,
+
+                                // Calculate later
+                                way_ids: Vec::new(),
+                                modified: true,
+                                problems: Vec::new(),
+                            },
+                        );
+                        insert_nodes
+                            .entry(near_miss.target)
+                            .or_default()
+                            .push((node_id, near_miss.snapped));
+                        node_id
+                    });
+
+                    if near_miss.node == node_ids[0] {
+                        node_ids.insert(0, node_id);
+                    } else {
+                        node_ids.push(node_id);
+                    }
+                }
+                self.change_way_nodes.insert(way, node_ids);
+                for (way_id, nodes) in insert_nodes {
+                    self.insert_nodes_into_way(model, way_id, nodes);
+                }
+            }
         }
         Ok(())
     }
@@ -497,9 +639,7 @@ impl Edits {
 
         // Modify existing ways first
         for (way_id, insert_points) in results.insert_new_nodes {
-            let mut node_ids = model.derived_ways[&way_id].node_ids.clone();
-            let mut linestring = model.derived_ways[&way_id].linestring.clone();
-
+            let mut nodes = Vec::new();
             for (pt, tags) in insert_points {
                 let node_id = self.new_node_id();
                 self.new_nodes.insert(
@@ -516,24 +656,13 @@ impl Edits {
                     },
                 );
                 node_mapping.insert(HashedPoint::new(pt), node_id);
-
-                // Figure out where in this way to insert this node. insert_points could be in any
-                // order.
-                let Some((idx, _)) = linestring.lines().enumerate().min_by_key(|(_, line)| {
-                    (Euclidean.distance(line, &Point::from(pt)) * 10e6) as usize
-                }) else {
-                    unreachable!("Couldn't find the line on a way to insert a node");
-                };
-
-                node_ids.insert(idx + 1, node_id);
-                linestring.0.insert(idx + 1, pt);
+                nodes.push((node_id, pt));
             }
-
-            self.change_way_nodes.insert(way_id, node_ids);
+            self.insert_nodes_into_way(model, way_id, nodes);
         }
 
         // Create new geometry
-        for (linestring, new_tags) in results.new_ways {
+        for (linestring, new_tags, provenance) in results.new_ways {
             let mut node_ids = Vec::new();
             for pt in linestring.coords() {
                 let id = node_mapping
@@ -566,6 +695,7 @@ impl Edits {
                     linestring,
                     tags: new_tags,
                     version: 0,
+                    provenance,
 
                     kind: results.new_kind.clone(),
                     modified: true,
@@ -583,6 +713,31 @@ impl Edits {
         }
     }
 
+    /// Insert new nodes into an existing way, each on the closest line
+    fn insert_nodes_into_way(
+        &mut self,
+        model: &Speedwalk,
+        way_id: WayID,
+        nodes: Vec<(NodeID, Coord)>,
+    ) {
+        let mut node_ids = model.derived_ways[&way_id].node_ids.clone();
+        let mut linestring = model.derived_ways[&way_id].linestring.clone();
+
+        for (node_id, pt) in nodes {
+            // Figure out where in this way to insert this node. The nodes could be in any order.
+            let Some((idx, _)) = linestring.lines().enumerate().min_by_key(|(_, line)| {
+                (Euclidean.distance(line, &Point::from(pt)) * 10e6) as usize
+            }) else {
+                unreachable!("Couldn't find the line on a way to insert a node");
+            };
+
+            node_ids.insert(idx + 1, node_id);
+            linestring.0.insert(idx + 1, pt);
+        }
+
+        self.change_way_nodes.insert(way_id, node_ids);
+    }
+
     pub fn to_osc(&self, model: &Speedwalk) -> String {
         let mut out = vec![r#"<osmChange version="0.6" generator="Speedwalk">"#.to_string()];
 
@@ -614,6 +769,18 @@ impl Edits {
         out.push("  </create>".to_string());
 
         out.push("  <modify>".to_string());
+        for id in self.change_node_tags.keys() {
+            let node = &model.derived_nodes[id];
+            let pt = model.mercator.pt_to_wgs84(node.pt);
+            out.push(format!(
+                r#"    <node id="{}" version="{}" lon="{}" lat="{}">"#,
+                id.0, node.version, pt.x, pt.y
+            ));
+            for (k, v) in &node.tags.0 {
+                out.push(format!(r#"      <tag k="{k}" v="{}" />"#, escape(v)));
+            }
+            out.push("    </node>".to_string());
+        }
         for id in union_keys(&self.change_way_tags, &self.change_way_nodes) {
             let way = &model.derived_ways[&id];
 
@@ -666,6 +833,21 @@ impl Edits {
             });
         }
 
+        for id in self.change_node_tags.keys() {
+            let node = &model.derived_nodes[id];
+            let pt = model.mercator.pt_to_wgs84(node.pt);
+            out.modify.push(OsmElement {
+                r#type: "node",
+                id: id.0,
+                tags: node.tags.0.clone(),
+                version: node.version,
+
+                lon: Some(pt.x),
+                lat: Some(pt.y),
+                nodes: Vec::new(),
+            });
+        }
+
         for id in union_keys(&self.change_way_tags, &self.change_way_nodes) {
             let way = &model.derived_ways[&id];
             out.modify.push(OsmElement {
@@ -687,8 +869,8 @@ impl Edits {
 impl Speedwalk {
     // TODO Or do this as we apply each UserCmd?
     pub fn after_edit(&mut self) {
-        self.derived_nodes = self.original_nodes.clone();
-        self.derived_ways = self.original_ways.clone();
+        let old_nodes = std::mem::replace(&mut self.derived_nodes, self.original_nodes.clone());
+        let old_ways = std::mem::replace(&mut self.derived_ways, self.original_ways.clone());
 
         let edits = self.edits.as_ref().unwrap();
 
@@ -700,6 +882,20 @@ impl Speedwalk {
             self.derived_ways.insert(*id, way.clone());
         }
 
+        for (node, cmds) in &edits.change_node_tags {
+            let node = self.derived_nodes.get_mut(node).unwrap();
+            for cmd in cmds {
+                match cmd {
+                    TagCmd::Set(k, v) => {
+                        node.tags.insert(k, v);
+                    }
+                    TagCmd::Remove(k) => {
+                        node.tags.remove(k);
+                    }
+                }
+            }
+            node.modified = true;
+        }
         for (way, cmds) in &edits.change_way_tags {
             let way = self.derived_ways.get_mut(way).unwrap();
             for cmd in cmds {
@@ -750,7 +946,14 @@ impl Speedwalk {
             node.way_ids.dedup();
         }
 
-        self.recalculate_problems();
+        // When the crossing options change, crossings anywhere could be judged differently
+        let crossing_options = self.last_crossing_options();
+        if crossing_options != self.crossing_options {
+            self.crossing_options = crossing_options;
+            self.recalculate_problems();
+        } else {
+            self.recalculate_problems_after(old_nodes, old_ways);
+        }
     }
 }
 
@@ -808,7 +1011,7 @@ fn escape(v: &str) -> String {
 }
 
 pub struct CreateNewGeometry {
-    pub new_ways: Vec<(LineString, Tags)>,
+    pub new_ways: Vec<(LineString, Tags, Provenance)>,
     /// All of the new ways have the same Kind
     pub new_kind: Kind,
     /// Insert new nodes into an existing way
@@ -826,6 +1029,7 @@ fn is_oneway(tags: &Tags) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::crossings::DEFAULT_MAX_SNAP_DISTANCE_METERS;
     use std::collections::HashSet;
 
     fn model_from_osm(osm: &str) -> Speedwalk {
@@ -850,6 +1054,7 @@ mod tests {
             &model,
             Point::new(0.000020, 0.000010),
             Point::new(0.000160, -0.000010),
+            DEFAULT_MAX_SNAP_DISTANCE_METERS,
         )
         .unwrap();
         assert_ne!(snapped.0, snapped.1);
@@ -885,6 +1090,7 @@ mod tests {
             &model,
             Point::new(0.000020, 0.000010),
             Point::new(0.000250, -0.000010),
+            DEFAULT_MAX_SNAP_DISTANCE_METERS,
         )
         .unwrap();
 
@@ -925,6 +1131,7 @@ mod tests {
             &model,
             Point::new(0.000010, 0.000000), // near way 100
             Point::new(0.000170, 0.000090), // near way 200 (different way)
+            DEFAULT_MAX_SNAP_DISTANCE_METERS,
         )
         .unwrap();
         assert!(
@@ -932,4 +1139,137 @@ mod tests {
             "Resolver should keep first snapped way and project second point onto it"
         );
     }
+
+    #[test]
+    fn generated_provenance_not_written_to_osmchange() {
+        let osm = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <node id="1" lon="0.000000" lat="0.000000" version="1" />
+  <node id="2" lon="0.000450" lat="0.000000" version="1">
+    <tag k="highway" v="crossing"/>
+    <tag k="crossing" v="uncontrolled"/>
+  </node>
+  <node id="3" lon="0.000900" lat="0.000000" version="1" />
+  <way id="100" version="1">
+    <nd ref="1"/><nd ref="2"/><nd ref="3"/>
+    <tag k="highway" v="residential"/>
+    <tag k="sidewalk" v="both"/>
+  </way>
+</osm>"#;
+        let mut model = model_from_osm(osm);
+        let mut edits = model.take_edits();
+        edits
+            .apply_cmd(
+                UserCmd::MakeAllSidewalks(false, SidewalkOptions::default()),
+                &model,
+            )
+            .unwrap();
+        model.set_edits(edits);
+        model.after_edit();
+        let mut edits = model.take_edits();
+        edits
+            .apply_cmd(
+                UserCmd::ConnectAllCrossings(false, CrossingOptions::default()),
+                &model,
+            )
+            .unwrap();
+        model.set_edits(edits);
+        model.after_edit();
+
+        let crossing = model
+            .derived_ways
+            .values()
+            .find(|w| w.kind == Kind::Crossing)
+            .expect("a crossing way should be generated");
+        assert_eq!(crossing.provenance.osm_node, Some(NodeID(2)));
+        assert!(
+            model
+                .derived_ways
+                .values()
+                .any(|w| w.kind == Kind::Sidewalk && w.provenance.osm_way == Some(WayID(100)))
+        );
+
+        let edits = model.take_edits();
+        assert!(!edits.to_osc(&model).contains("tmp:"));
+        assert!(!edits.to_osmchange_json(&model).unwrap().contains("tmp:"));
+    }
+
+    #[test]
+    fn incremental_problems_match_full_recalculation() {
+        // Two far apart clusters, each with problems
+        let osm = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <node id="1" lon="0.000000" lat="0.000000" version="1" />
+  <node id="2" lon="0.000450" lat="0.000000" version="1">
+    <tag k="highway" v="crossing"/>
+    <tag k="crossing" v="uncontrolled"/>
+  </node>
+  <node id="3" lon="0.000900" lat="0.000000" version="1" />
+  <node id="4" lon="0.000000" lat="0.000100" version="1" />
+  <node id="5" lon="0.000900" lat="0.000100" version="1" />
+  <node id="11" lon="0.020000" lat="0.000000" version="1" />
+  <node id="12" lon="0.020900" lat="0.000000" version="1" />
+  <way id="100" version="1">
+    <nd ref="1"/><nd ref="2"/><nd ref="3"/>
+    <tag k="highway" v="residential"/>
+    <tag k="sidewalk" v="separate"/>
+  </way>
+  <way id="101" version="1">
+    <nd ref="4"/><nd ref="5"/>
+    <tag k="highway" v="footway"/>
+    <tag k="footway" v="sidewalk"/>
+  </way>
+  <way id="200" version="1">
+    <nd ref="11"/><nd ref="12"/>
+    <tag k="highway" v="residential"/>
+    <tag k="sidewalk" v="separate"/>
+  </way>
+</osm>"#;
+        let mut model = model_from_osm(osm);
+
+        let snapshot = |model: &Speedwalk| {
+            let mut out = Vec::new();
+            for (id, node) in &model.derived_nodes {
+                for p in &node.problems {
+                    out.push((
+                        format!("node/{}", id.0),
+                        p.problem_type.code(),
+                        p.note.clone(),
+                    ));
+                }
+            }
+            for (id, way) in &model.derived_ways {
+                for p in &way.problems {
+                    out.push((
+                        format!("way/{}", id.0),
+                        p.problem_type.code(),
+                        p.note.clone(),
+                    ));
+                }
+            }
+            out.sort();
+            out
+        };
+
+        for cmd in [
+            UserCmd::SetTags {
+                way: WayID(100),
+                remove_keys: vec!["sidewalk".to_string()],
+                add_tags: vec![("sidewalk:right".to_string(), "separate".to_string())],
+            },
+            UserCmd::MakeAllSidewalks(false, SidewalkOptions::default()),
+        ] {
+            let mut edits = model.take_edits();
+            edits.apply_cmd(cmd, &model).unwrap();
+            model.set_edits(edits);
+            model.after_edit();
+
+            let incremental = snapshot(&model);
+            model.recalculate_problems();
+            assert_eq!(incremental, snapshot(&model));
+        }
+
+        // The far cluster kept its problem
+        assert!(!model.derived_ways[&WayID(200)].problems.is_empty());
+    }
 }
@@ -1,11 +1,14 @@
 use anyhow::Result;
-use geo::{Euclidean, Length};
+use geo::{Coord, Euclidean, Length, LineString};
 use geojson::GeoJson;
+use osm_reader::{NodeID, WayID};
 use serde::Deserialize;
 use std::collections::{HashMap, HashSet};
+use utils::Tags;
 
 use crate::{
-    Kind, Speedwalk,
+    Kind, Provenance, Side, Speedwalk, Way,
+    crossings::{CrossingOptions, crossing_way_tags},
     graph::{Edge, EdgeID, Graph, IntersectionID},
 };
 
@@ -32,10 +35,16 @@ const MINIMUM_DEADEND_LENGTH: f64 = 10.0;
 const MINIMUM_DISCONNECTED_LENGTH: f64 = 100.0;
 
 impl Speedwalk {
-    fn edge_is_manual_crossing(&self, edge: &Edge) -> bool {
-        self.derived_ways[&edge.osm_way]
-            .tags
-            .is("crossing", "manual")
+    /// The way an edge came from, which may be virtual
+    fn edge_way<'a>(&'a self, graph: &'a Graph, edge: &Edge) -> &'a Way {
+        graph
+            .virtual_ways
+            .get(&edge.osm_way)
+            .unwrap_or_else(|| &self.derived_ways[&edge.osm_way])
+    }
+
+    fn edge_is_manual_crossing(&self, graph: &Graph, edge: &Edge) -> bool {
+        self.edge_way(graph, edge).tags.is("crossing", "manual")
     }
 
     pub(crate) fn edge_is_manually_deleted(&self, edge: &Edge) -> bool {
@@ -52,13 +61,13 @@ impl Speedwalk {
     fn filter_network_without_deadends(
         &self,
         filter: &NetworkFilter,
-        _graph: &Graph,
+        graph: &Graph,
         edge: &Edge,
     ) -> bool {
         if self.edge_is_manually_deleted(edge) {
             return false;
         }
-        let way = &self.derived_ways[&edge.osm_way];
+        let way = self.edge_way(graph, edge);
 
         match filter.include {
             NetworkFilterType::Everything => {}
@@ -198,7 +207,7 @@ impl Speedwalk {
                         // Reached another endpoint or a branching intersection.
                         if chain_length < MINIMUM_DEADEND_LENGTH {
                             dead_end_edges.extend(chain_edges.iter().copied().filter(|edge_id| {
-                                !self.edge_is_manual_crossing(&graph.edges[edge_id])
+                                !self.edge_is_manual_crossing(graph, &graph.edges[edge_id])
                             }));
                         }
                         break;
@@ -209,7 +218,7 @@ impl Speedwalk {
                     if visited_edges.contains(&next_id) {
                         if chain_length < MINIMUM_DEADEND_LENGTH {
                             dead_end_edges.extend(chain_edges.iter().copied().filter(|edge_id| {
-                                !self.edge_is_manual_crossing(&graph.edges[edge_id])
+                                !self.edge_is_manual_crossing(graph, &graph.edges[edge_id])
                             }));
                         }
                         break;
@@ -262,6 +271,7 @@ impl Speedwalk {
 
             let start_edge = &graph.edges[&start_edge_id];
             stack.push(start_edge.src);
+            stack.push(start_edge.dst);
             component_intersections.insert(start_edge.src);
             component_intersections.insert(start_edge.dst);
             component_edges.push(start_edge_id);
@@ -305,10 +315,9 @@ impl Speedwalk {
             // If component is < 100m, remove all edges in it
             if component_length < MINIMUM_DISCONNECTED_LENGTH {
                 dead_end_edges.extend(
-                    component_edges
-                        .iter()
-                        .copied()
-                        .filter(|edge_id| !self.edge_is_manual_crossing(&graph.edges[edge_id])),
+                    component_edges.iter().copied().filter(|edge_id| {
+                        !self.edge_is_manual_crossing(graph, &graph.edges[edge_id])
+                    }),
                 );
             }
         }
@@ -328,7 +337,7 @@ impl Speedwalk {
         }
         // Manual crossing segments stay visible, but are still included in topology when
         // computing dead-end/disconnected filtering.
-        if self.edge_is_manual_crossing(edge) {
+        if self.edge_is_manual_crossing(graph, edge) {
             return true;
         }
         // Apply filters without dead end check
@@ -357,7 +366,14 @@ impl Speedwalk {
     }
 
     pub fn export_network(&self, filter: NetworkFilter) -> Result<String> {
-        let graph = Graph::new(self);
+        // Sidewalks tagged on a road are represented by the road's own edges. Crossings on those
+        // roads get virtual crossing edges between synthetic sidewalk vertices, joined to the
+        // road, so the network looks the same as if the sidewalks were mapped separately.
+        let graph = if matches!(filter.include, NetworkFilterType::OnlyExplicitFootways) {
+            Graph::new(self)
+        } else {
+            self.graph_with_virtual_crossings()
+        };
 
         // If dead end filtering is enabled, use chain-based approach
         let dead_end_edges = if filter.ignore_deadends() {
@@ -370,25 +386,28 @@ impl Speedwalk {
         for edge in graph.edges.values() {
             if self.filter_network(&filter, &graph, edge, dead_end_edges.as_ref()) {
                 let mut f = self.mercator.to_wgs84_gj(&edge.linestring);
-                let way = &self.derived_ways[&edge.osm_way];
+                let way = self.edge_way(&graph, edge);
 
                 f.set_property("node1", edge.osm_node1.0);
                 f.set_property("node2", edge.osm_node2.0);
                 f.set_property("way", edge.osm_way.0);
 
-                // Determine osm_id from tags stored during generation, or fallback to way ID
-                if let Some(osm_node_id) = way.tags.get("tmp:osm_node_id") {
+                // Determine osm_id from where generated geometry came from, or fallback to way ID
+                if let Some(osm_node_id) = way.provenance.osm_node {
                     // OSM node reference (for crossings)
-                    f.set_property("osm_id", osm_node_id.clone());
-                } else if let Some(osm_way_id) = way.tags.get("tmp:osm_way_id") {
+                    f.set_property("osm_id", format!("node/{}", osm_node_id.0));
+                } else if let Some(osm_way_id) = way.provenance.osm_way {
                     // OSM way reference (for sidewalks and crossings fallback)
-                    f.set_property("osm_id", osm_way_id.clone());
+                    f.set_property("osm_id", format!("way/{}", osm_way_id.0));
                 } else {
                     // Regular way - use the way ID (always available as final fallback)
                     f.set_property("osm_id", format!("way/{}", edge.osm_way.0));
                 }
 
                 f.set_property("kind", format!("{:?}", way.kind));
+                if graph.virtual_ways.contains_key(&edge.osm_way) {
+                    f.set_property("virtual", true);
+                }
                 let length = Euclidean.length(&edge.linestring);
                 f.set_property("length", (length * 100.0).round() / 100.0);
 
@@ -399,8 +418,91 @@ impl Speedwalk {
                 features.push(f);
             }
         }
+
         Ok(serde_json::to_string(&GeoJson::from(features))?)
     }
+
+    /// The graph, plus a virtual crossing way across the road at each `virtual_crossings` node,
+    /// and a virtual sidewalk way joining each tagged side of it to the crossing node. Virtual
+    /// IDs are below every ID in use.
+    fn graph_with_virtual_crossings(&self) -> Graph {
+        let options = CrossingOptions::default();
+        let mut next_node = self
+            .derived_nodes
+            .keys()
+            .map(|n| n.0)
+            .min()
+            .unwrap_or(0)
+            .min(0);
+        let mut next_way = self
+            .derived_ways
+            .keys()
+            .map(|w| w.0)
+            .min()
+            .unwrap_or(0)
+            .min(0);
+        let mut nodes: HashMap<NodeID, Coord> = HashMap::new();
+        let mut ways: HashMap<WayID, Way> = HashMap::new();
+        let mut add_way = |node_ids: Vec<NodeID>, pts: Vec<Coord>, tags: Tags, provenance| {
+            next_way -= 1;
+            ways.insert(
+                WayID(next_way),
+                Way {
+                    node_ids,
+                    linestring: LineString::new(pts),
+                    kind: Kind::classify(&tags),
+                    tags,
+                    version: 0,
+                    provenance,
+                    modified: false,
+                    problems: Vec::new(),
+                },
+            );
+        };
+
+        for crossing in self.virtual_crossings() {
+            let node = &self.derived_nodes[&crossing.node];
+            let mut side_node = |pt| {
+                next_node -= 1;
+                nodes.insert(NodeID(next_node), pt);
+                NodeID(next_node)
+            };
+            let left = side_node(crossing.left);
+            let right = side_node(crossing.right);
+
+            add_way(
+                vec![left, right],
+                vec![crossing.left, crossing.right],
+                crossing_way_tags(node, &options),
+                Provenance {
+                    osm_node: Some(crossing.node),
+                    osm_way: Some(crossing.road),
+                    side: None,
+                },
+            );
+            for side in crossing.tagged_sides {
+                let (id, pt) = match side {
+                    Side::Left => (left, crossing.left),
+                    Side::Right => (right, crossing.right),
+                };
+                let mut tags = Tags::empty();
+                tags.insert("highway", "footway");
+                tags.insert("footway", "sidewalk");
+                add_way(
+                    vec![id, crossing.node],
+                    vec![pt, node.pt],
+                    tags,
+                    Provenance {
+                        osm_node: None,
+                        osm_way: Some(crossing.road),
+                        side: Some(side),
+                    },
+                );
+            }
+        }
+
+        Graph::new_with_virtual(self, nodes, ways)
+    }
 }
 
 #[cfg(test)]
@@ -421,6 +523,39 @@ mod tests {
         Speedwalk::new_from_osm(osm.as_bytes(), None).unwrap()
     }
 
+    #[test]
+    fn disconnected_component_includes_edges_past_start_edge_dst() {
+        // Three 40m footways all point into node 1. Together they're long enough to keep, but
+        // each one alone isn't.
+        let osm = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <node id="1" lon="0.000000" lat="0.000000" version="1" />
+  <node id="2" lon="0.000360" lat="0.000000" version="1" />
+  <node id="3" lon="-0.000360" lat="0.000000" version="1" />
+  <node id="4" lon="0.000000" lat="0.000360" version="1" />
+  <way id="100" version="1">
+    <nd ref="2"/><nd ref="1"/>
+    <tag k="highway" v="footway"/>
+  </way>
+  <way id="101" version="1">
+    <nd ref="3"/><nd ref="1"/>
+    <tag k="highway" v="footway"/>
+  </way>
+  <way id="102" version="1">
+    <nd ref="4"/><nd ref="1"/>
+    <tag k="highway" v="footway"/>
+  </way>
+</osm>"#;
+        let model = model_from_osm(osm);
+        let graph = Graph::new(&model);
+        assert_eq!(graph.edges.len(), 3);
+        assert!(
+            model
+                .find_dead_end_chains(&test_filter(), &graph)
+                .is_empty()
+        );
+    }
+
     #[test]
     fn manual_delete_edge_excluded_from_export_network() {
         let osm = r#"<?xml version="1.0" encoding="UTF-8"?>
@@ -583,4 +718,93 @@ mod tests {
         // disconnected-component pass.
         assert_eq!(dead.len(), graph.edges.len());
     }
+
+    #[test]
+    fn virtual_crossing_on_road_with_tagged_sidewalks() {
+        // Crossing node 2 is on a road with tagged sidewalks. Crossing node 5 already has a
+        // footway through it.
+        let osm = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <node id="1" lon="0.000000" lat="0.000000" version="1" />
+  <node id="2" lon="0.000900" lat="0.000000" version="1">
+    <tag k="highway" v="crossing"/>
+    <tag k="crossing" v="uncontrolled"/>
+  </node>
+  <node id="3" lon="0.001800" lat="0.000000" version="1" />
+  <node id="4" lon="0.000000" lat="0.001000" version="1" />
+  <node id="5" lon="0.000900" lat="0.001000" version="1">
+    <tag k="highway" v="crossing"/>
+  </node>
+  <node id="6" lon="0.001800" lat="0.001000" version="1" />
+  <node id="7" lon="0.000900" lat="0.000800" version="1" />
+  <node id="8" lon="0.000900" lat="0.001200" version="1" />
+  <way id="100" version="1">
+    <nd ref="1"/><nd ref="2"/><nd ref="3"/>
+    <tag k="highway" v="residential"/>
+    <tag k="sidewalk" v="both"/>
+  </way>
+  <way id="101" version="1">
+    <nd ref="4"/><nd ref="5"/><nd ref="6"/>
+    <tag k="highway" v="residential"/>
+    <tag k="sidewalk" v="both"/>
+  </way>
+  <way id="200" version="1">
+    <nd ref="7"/><nd ref="5"/><nd ref="8"/>
+    <tag k="highway" v="footway"/>
+  </way>
+</osm>"#;
+        let model = model_from_osm(osm);
+        let node = |f: &geojson::Feature, key| f.property(key).unwrap().as_i64().unwrap();
+        for ignore_deadends in [false, true] {
+            let gj: GeoJson = model
+                .export_network(NetworkFilter {
+                    include: NetworkFilterType::RouteableNetwork,
+                    ignore_deadends,
+                })
+                .unwrap()
+                .parse()
+                .unwrap();
+            let GeoJson::FeatureCollection(fc) = gj else {
+                panic!("expected a FeatureCollection");
+            };
+            let (virtual_crossings, others): (Vec<_>, Vec<_>) = fc.features.iter().partition(|f| {
+                f.property("virtual").is_some() && f.property("kind").unwrap() == "Crossing"
+            });
+            assert_eq!(virtual_crossings.len(), 1);
+            let f = virtual_crossings[0];
+            assert_eq!(f.property("osm_id").unwrap(), "node/2");
+            assert_eq!(f.property("footway").unwrap(), "crossing");
+            assert_eq!(f.property("crossing").unwrap(), "uncontrolled");
+            assert_eq!(f.property("length").unwrap().as_f64(), Some(6.0));
+
+            // Both ends of the crossing are synthetic vertices, reachable from the far end of the
+            // road without using the crossing
+            let (left, right) = (node(f, "node1"), node(f, "node2"));
+            assert!(left < 0 && right < 0);
+            let mut reached = HashSet::from([1]);
+            loop {
+                let before = reached.len();
+                for x in &others {
+                    let (n1, n2) = (node(x, "node1"), node(x, "node2"));
+                    if reached.contains(&n1) || reached.contains(&n2) {
+                        reached.insert(n1);
+                        reached.insert(n2);
+                    }
+                }
+                if reached.len() == before {
+                    break;
+                }
+            }
+            assert!(reached.contains(&left) && reached.contains(&right));
+        }
+
+        // Not part of the explicit footway network
+        let explicit = model
+            .export_network(NetworkFilter {
+                include: NetworkFilterType::OnlyExplicitFootways,
+                ignore_deadends: false,
+            })
+            .unwrap();
+        assert!(!explicit.contains("virtual"));
+    }
 }
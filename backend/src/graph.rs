@@ -1,15 +1,17 @@
 use std::collections::{BTreeMap, HashMap};
 
-use geo::{LineString, Point};
+use geo::{Coord, LineString, Point};
 use osm_reader::{NodeID, WayID};
 use utils::Tags;
 
-use crate::Speedwalk;
+use crate::{Speedwalk, Way};
 
 // TODO Adapted from utils::osm2graph. Not sure we need all of this.
 pub struct Graph {
     pub edges: BTreeMap<EdgeID, Edge>,
     pub intersections: BTreeMap<IntersectionID, Intersection>,
+    /// Ways that aren't part of the model, but are in the graph
+    pub virtual_ways: HashMap<WayID, Way>,
 }
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord)]
@@ -48,9 +50,25 @@ pub struct Intersection {
 
 impl Graph {
     pub fn new(osm: &Speedwalk) -> Self {
+        Self::new_with_virtual(osm, HashMap::new(), HashMap::new())
+    }
+
+    /// Also includes ways that aren't part of the model, using nodes that may not be either
+    pub fn new_with_virtual(
+        osm: &Speedwalk,
+        virtual_nodes: HashMap<NodeID, Coord>,
+        virtual_ways: HashMap<WayID, Way>,
+    ) -> Self {
+        let pt = |node: &NodeID| {
+            virtual_nodes
+                .get(node)
+                .copied()
+                .unwrap_or_else(|| osm.derived_nodes[node].pt)
+        };
+
         // Count how many ways reference each node
         let mut node_counter: HashMap<NodeID, usize> = HashMap::new();
-        for way in osm.derived_ways.values() {
+        for way in osm.derived_ways.values().chain(virtual_ways.values()) {
             for node in &way.node_ids {
                 *node_counter.entry(*node).or_insert(0) += 1;
             }
@@ -61,7 +79,7 @@ impl Graph {
         let mut node_to_intersection: HashMap<NodeID, IntersectionID> = HashMap::new();
         let mut intersections = BTreeMap::new();
         let mut edges = BTreeMap::new();
-        for (way_id, way) in &osm.derived_ways {
+        for (way_id, way) in osm.derived_ways.iter().chain(virtual_ways.iter()) {
             let mut node1 = way.node_ids[0];
             let mut idx_of_node1 = 0;
             let mut pts = Vec::new();
@@ -69,7 +87,7 @@ impl Graph {
 
             let num_nodes = way.node_ids.len();
             for (idx, node) in way.node_ids.iter().cloned().enumerate() {
-                pts.push(osm.derived_nodes[&node].pt);
+                pts.push(pt(&node));
                 nodes.push(node);
                 // Edges start/end at intersections between two ways. The endpoints of the way also
                 // count as intersections.
@@ -123,7 +141,7 @@ impl Graph {
                     nodes.push(node);
                     node1 = node;
                     idx_of_node1 = idx;
-                    pts.push(osm.derived_nodes[&node].pt);
+                    pts.push(pt(&node));
                 }
             }
         }
@@ -131,6 +149,7 @@ impl Graph {
         Self {
             edges,
             intersections,
+            virtual_ways,
         }
     }
 }
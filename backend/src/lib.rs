@@ -4,19 +4,27 @@ extern crate anyhow;
 #[macro_use]
 extern crate log;
 
+mod accessibility;
 mod audit;
+mod challenge;
 mod classify;
+mod corner_crossings;
+mod crossing_spacing;
+mod crossing_tags;
 mod crossings;
 mod disconnected;
+mod duplicate_sidewalks;
 mod edits;
 mod export;
 mod graph;
 mod make_sidewalks;
+mod near_misses;
 mod problems;
 mod scrape;
+mod unsplit_crossings;
 mod wasm;
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
 use anyhow::Result;
 use geo::{Coord, LineString, Polygon};
@@ -27,7 +35,11 @@ use serde::Serialize;
 use utils::{Mercator, Tags};
 use wasm_bindgen::prelude::*;
 
+use crate::crossings::CrossingOptions;
+
 pub use crate::classify::Kind;
+pub use crate::make_sidewalks::Side;
+pub use crate::problems::{ProblemSuppression, ProblemType};
 
 /// Highway types treated as severance (major roads that cut through). Used for both
 /// Way::is_severance() and RoadWithoutSidewalksImplicit; the latter also includes "service".
@@ -66,6 +78,12 @@ pub struct Speedwalk {
     closest_building: RTree<Polygon>,
 
     edits: Option<Edits>,
+    /// Problems marked as not an issue. Unlike edits, these don't change the data.
+    suppressed_problems: BTreeSet<ProblemSuppression>,
+    /// Also look for accessibility problems on crossings
+    check_accessibility: bool,
+    /// From the last crossing run. Unconnected crossing nodes are judged with these.
+    crossing_options: CrossingOptions,
 
     derived_nodes: HashMap<NodeID, Node>,
     derived_ways: HashMap<WayID, Way>,
@@ -105,6 +123,8 @@ pub struct Way {
     pub linestring: LineString,
     pub tags: Tags,
     pub version: i32,
+    /// Only set for generated ways. Never part of the tags, so it can't be uploaded to OSM.
+    pub provenance: Provenance,
 
     // TODO Manage derived state better. Everything below is suspect.
     pub kind: Kind,
@@ -171,8 +191,47 @@ impl Way {
     }
 }
 
+/// Where a generated way came from
+#[derive(Clone, Default)]
+pub struct Provenance {
+    /// The crossing node a generated crossing way was made from
+    pub osm_node: Option<NodeID>,
+    /// The road a generated sidewalk or crossing way was made from
+    pub osm_way: Option<WayID>,
+    /// Which side of `osm_way` a generated sidewalk is on
+    pub side: Option<Side>,
+}
+
 #[derive(Clone, Serialize)]
 pub struct Problem {
+    #[serde(flatten)]
+    pub problem_type: ProblemType,
     pub note: String,
     pub details: Vec<Feature>,
+    /// Marked as not an issue
+    pub suppressed: bool,
+    /// Tags to change on the element to fix the problem, if it's that simple
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag_fix: Option<TagFix>,
+}
+
+/// Changes to one element's tags, matching `UserCmd::SetTags` and `UserCmd::SetNodeTags`
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct TagFix {
+    pub remove_keys: Vec<String>,
+    pub add_tags: Vec<(String, String)>,
+}
+
+impl TagFix {
+    /// Returns the tags after the fix
+    pub fn apply(&self, tags: &Tags) -> Tags {
+        let mut tags = tags.clone();
+        for key in &self.remove_keys {
+            tags.remove(key);
+        }
+        for (k, v) in &self.add_tags {
+            tags.insert(k, v);
+        }
+        tags
+    }
 }
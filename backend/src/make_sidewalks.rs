@@ -1,28 +1,216 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use anyhow::Result;
 
 use geo::buffer::{BufferStyle, LineJoin};
 use geo::line_intersection::{LineIntersection, line_intersection};
 use geo::{
-    Buffer, Coord, Euclidean, InterpolatableLine, Line, LineLocatePoint, LineString,
-    MultiLineString, Point,
+    BooleanOps, Buffer, Coord, Distance, Euclidean, InterpolatableLine, InterpolatePoint, Length,
+    Line, LineLocatePoint, LineString, MultiLineString, Point,
 };
-use osm_reader::WayID;
+use geojson::GeoJson;
+use osm_reader::{NodeID, WayID};
 use rstar::{RTree, primitives::GeomWithData};
-use utils::{Tags, aabb};
+use serde::{Deserialize, Serialize};
+use utils::{OffsetCurve, Tags, aabb, buffer_aabb};
 
 use crate::{
-    Kind, Speedwalk,
+    Kind, Provenance, Speedwalk,
     edits::{CreateNewGeometry, TagCmd},
 };
 
-const BUFFER_DISTANCE: f64 = 3.0;
+pub(crate) const BUFFER_DISTANCE: f64 = 3.0;
+/// When offsetting each road separately, sidewalks stop slightly before reaching the sidewalks of
+/// other roads, so a sidewalk just touching the end of another road isn't split there.
+const TRIM_DISTANCE: f64 = BUFFER_DISTANCE - 0.1;
+/// When offsetting each road separately, sidewalk ends this close together are joined.
+const JOIN_ENDPOINTS_DISTANCE: f64 = 1.0;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
-enum Side {
+pub enum Side {
     Left,
     Right,
 }
 
+impl Side {
+    pub(crate) fn key(self) -> &'static str {
+        match self {
+            Side::Left => "left",
+            Side::Right => "right",
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SidewalkOptions {
+    #[serde(default)]
+    pub method: GenerationMethod,
+    /// Which attributes of the road are copied onto the sidewalks generated for it
+    #[serde(default = "default_inherit_tags")]
+    pub inherit_tags: Vec<InheritTag>,
+    #[serde(default)]
+    pub split_at: SplitAt,
+    /// Additionally split sidewalks evenly, so no piece is longer than this
+    #[serde(default)]
+    pub max_length_meters: Option<f64>,
+    /// At sharp bends, the midpoint of one segment of a new sidewalk can be closer to a different
+    /// road than its neighbors. Runs of up to this many segments surrounded by segments matched to
+    /// one road get matched to that road too. 0 disables this.
+    #[serde(default = "default_max_stray_segments")]
+    pub max_stray_segments: usize,
+}
+
+impl Default for SidewalkOptions {
+    fn default() -> Self {
+        Self {
+            method: GenerationMethod::default(),
+            inherit_tags: default_inherit_tags(),
+            split_at: SplitAt::default(),
+            max_length_meters: None,
+            max_stray_segments: default_max_stray_segments(),
+        }
+    }
+}
+
+fn default_max_stray_segments() -> usize {
+    1
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum GenerationMethod {
+    /// Buffer all roads together and split the outline of that blob into sidewalks. Roads with
+    /// unknown sidewalks get them on both sides.
+    #[default]
+    BufferAllRoads,
+    /// Offset each road separately, only on sides explicitly tagged with a sidewalk, and trim where
+    /// roads meet. This is more predictable where roads of very different widths meet, but roads
+    /// without sidewalk tags get nothing.
+    OffsetEachRoad,
+}
+
+/// Where to split the buffered outline into separate sidewalk ways. The side of the road always
+/// splits.
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum SplitAt {
+    /// Wherever the closest road changes
+    #[default]
+    RoadChanges,
+    /// Wherever the closest road changes, and at every junction along that road
+    Junctions,
+    /// Only where the attributes copied from the closest road change
+    AttributeChanges,
+}
+
+#[derive(PartialEq)]
+enum ChunkKey {
+    Road(WayID, Side),
+    RoadSection(WayID, Side, usize),
+    Attributes(Side, BTreeMap<String, String>),
+}
+
+/// One row of the mapping table from road tags to generated sidewalk tags.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct InheritTag {
+    /// When `per_side` is true, this is looked up as `sidewalk:<side>:<road_key>`, then
+    /// `sidewalk:both:<road_key>`, then `sidewalk:<road_key>`. Otherwise it's a plain key on the
+    /// road.
+    pub road_key: String,
+    pub per_side: bool,
+    pub sidewalk_key: String,
+}
+
+impl InheritTag {
+    fn per_side(key: &str) -> Self {
+        Self {
+            road_key: key.to_string(),
+            per_side: true,
+            sidewalk_key: key.to_string(),
+        }
+    }
+
+    fn whole_road(road_key: &str, sidewalk_key: &str) -> Self {
+        Self {
+            road_key: road_key.to_string(),
+            per_side: false,
+            sidewalk_key: sidewalk_key.to_string(),
+        }
+    }
+}
+
+fn default_inherit_tags() -> Vec<InheritTag> {
+    vec![
+        InheritTag::per_side("surface"),
+        InheritTag::per_side("width"),
+        InheritTag::per_side("smoothness"),
+        InheritTag::per_side("incline"),
+        InheritTag::per_side("kerb"),
+        InheritTag::whole_road("lit", "lit"),
+        InheritTag::whole_road("name", "street:name"),
+    ]
+}
+
+/// Copy attributes from a road onto a sidewalk generated on one side of it. If the sidewalk points
+/// the opposite way as the road, directional values like `incline` are flipped.
+fn inherit_tags(
+    mapping: &[InheritTag],
+    road: &Tags,
+    side: Side,
+    reversed: bool,
+    sidewalk: &mut Tags,
+) {
+    for row in mapping {
+        let value = if row.per_side {
+            [side.key(), "both"]
+                .into_iter()
+                .map(|s| format!("sidewalk:{s}:{}", row.road_key))
+                .chain(std::iter::once(format!("sidewalk:{}", row.road_key)))
+                .find_map(|key| road.get(&key))
+        } else {
+            road.get(&row.road_key)
+        };
+        let Some(value) = value else {
+            continue;
+        };
+        if reversed && row.sidewalk_key == "incline" {
+            sidewalk.insert(&row.sidewalk_key, reverse_incline(value));
+        } else {
+            sidewalk.insert(&row.sidewalk_key, value);
+        }
+    }
+}
+
+fn reverse_incline(value: &str) -> String {
+    match value {
+        "up" => "down".to_string(),
+        "down" => "up".to_string(),
+        _ => {
+            if let Some(positive) = value.strip_prefix('-') {
+                positive.to_string()
+            } else if value.starts_with(|c: char| c.is_ascii_digit()) {
+                format!("-{value}")
+            } else {
+                // Things like "yes" don't have a direction
+                value.to_string()
+            }
+        }
+    }
+}
+
+/// Does `sidewalk` point the opposite direction as `road`?
+fn runs_against(sidewalk: &LineString, road: &LineString) -> bool {
+    let (Some(first), Some(last)) = (sidewalk.points().next(), sidewalk.points().next_back())
+    else {
+        return false;
+    };
+    match (
+        road.line_locate_point(&first),
+        road.line_locate_point(&last),
+    ) {
+        (Some(start), Some(end)) => end < start,
+        _ => false,
+    }
+}
+
 /// Side allowance checks for roads that are not `Kind::RoadWithSeparate`.
 ///
 /// `separate`-specific semantics are handled elsewhere:
@@ -55,10 +243,100 @@ fn side_is_allowed_by_non_separate_tags(tags: &Tags, side: Side) -> bool {
 }
 
 impl Speedwalk {
-    pub fn make_all_sidewalks(&self, only_severances: bool) -> CreateNewGeometry {
+    pub fn make_all_sidewalks(
+        &self,
+        only_severances: bool,
+        options: &SidewalkOptions,
+    ) -> CreateNewGeometry {
+        self.make_sidewalks(only_severances, options, None)
+    }
+
+    /// Like `make_all_sidewalks`, but only keeps the new sidewalks at these indices of its result.
+    /// The indices are only meaningful for the same model and options they were previewed with.
+    pub fn make_selected_sidewalks(
+        &self,
+        only_severances: bool,
+        options: &SidewalkOptions,
+        selected: &[usize],
+    ) -> CreateNewGeometry {
+        self.make_sidewalks(only_severances, options, Some(selected))
+    }
+
+    /// Calculates `make_all_sidewalks` without applying it, returning GeoJSON with the new
+    /// sidewalks, the nodes inserted into existing ways, and tag changes to existing roads.
+    pub fn preview_make_all_sidewalks(
+        &self,
+        only_severances: bool,
+        options: &SidewalkOptions,
+    ) -> Result<String> {
+        let results = self.make_all_sidewalks(only_severances, options);
+        let mut features = Vec::new();
+
+        for (idx, (ls, tags, provenance)) in results.new_ways.iter().enumerate() {
+            let mut f = self.mercator.to_wgs84_gj(ls);
+            f.set_property("preview", "new_way");
+            f.set_property("index", idx);
+            f.set_property("tags", serde_json::to_value(tags)?);
+            if let (Some(way), Some(side)) = (provenance.osm_way, provenance.side) {
+                f.set_property("way", way.0);
+                f.set_property(
+                    "reason",
+                    format!("sidewalk on the {} of way {}", side.key(), way.0),
+                );
+            }
+            features.push(f);
+        }
+
+        for (way, pts) in &results.insert_new_nodes {
+            for (pt, _) in pts {
+                let mut f = self.mercator.to_wgs84_gj(&Point::from(*pt));
+                f.set_property("preview", "new_node");
+                f.set_property("way", way.0);
+                f.set_property("reason", format!("a new sidewalk crosses way {}", way.0));
+                features.push(f);
+            }
+        }
+
+        for (way, cmds) in &results.modify_existing_way_tags {
+            let mut f = self
+                .mercator
+                .to_wgs84_gj(&self.derived_ways[way].linestring);
+            f.set_property("preview", "tag_change");
+            f.set_property("way", way.0);
+            let mut remove = Vec::new();
+            let mut set = BTreeMap::new();
+            for cmd in cmds {
+                match cmd {
+                    TagCmd::Remove(k) => remove.push(k.clone()),
+                    TagCmd::Set(k, v) => {
+                        set.insert(k.clone(), v.clone());
+                    }
+                }
+            }
+            f.set_property("remove_keys", remove);
+            f.set_property("set_tags", serde_json::to_value(set)?);
+            f.set_property("reason", "the road gets separate sidewalks");
+            features.push(f);
+        }
+
+        Ok(serde_json::to_string(&GeoJson::from(features))?)
+    }
+
+    fn make_sidewalks(
+        &self,
+        only_severances: bool,
+        options: &SidewalkOptions,
+        selected: Option<&[usize]>,
+    ) -> CreateNewGeometry {
+        // Visit roads in a fixed order, so the same input always produces the same sidewalks in
+        // the same order
+        let mut ids: Vec<&WayID> = self.derived_ways.keys().collect();
+        ids.sort();
+
         let mut roads = Vec::new();
         let mut roads_with_ways = Vec::new();
-        for (id, way) in &self.derived_ways {
+        for id in ids {
+            let way = &self.derived_ways[id];
             if !matches!(
                 way.kind,
                 Kind::RoadWithSeparate | Kind::RoadWithTags | Kind::RoadUnknown
@@ -84,76 +362,50 @@ impl Speedwalk {
 
             roads_with_ways.push(GeomWithData::new(way.linestring.clone(), *id));
         }
-        let roads_mls = MultiLineString(roads);
-
-        // Buffer these all in one batch; it's much cleaner
-        info!("Creating one big buffered blob");
-        let subtract_polygons = roads_mls.buffer_with_style(
-            BufferStyle::new(BUFFER_DISTANCE).line_join(LineJoin::Round(BUFFER_DISTANCE)),
-        );
-
-        // Debugging
-        #[cfg(target_arch = "wasm32")]
-        if false {
-            utils::download_string(
-                &serde_json::to_string(&self.mercator.to_wgs84_gj(&subtract_polygons)).unwrap(),
-                "buffered.geojson",
-            )
-            .unwrap();
-        }
+        let closest_road = RTree::bulk_load(roads_with_ways);
 
-        let mut raw_new_sidewalks = Vec::new();
-        for polygon in subtract_polygons {
-            let (exterior, holes) = polygon.into_inner();
-            raw_new_sidewalks.push(exterior);
-            raw_new_sidewalks.extend(holes);
-        }
+        let pieces = match options.method {
+            GenerationMethod::BufferAllRoads => {
+                self.buffer_all_roads(MultiLineString(roads), &closest_road, options)
+            }
+            GenerationMethod::OffsetEachRoad => self.offset_each_road(&closest_road, options),
+        };
+        let pieces: Vec<_> = pieces
+            .into_iter()
+            .enumerate()
+            .filter(|(idx, _)| selected.is_none_or(|selected| selected.contains(idx)))
+            .map(|(_, piece)| piece)
+            .collect();
 
         let mut new_tags = Tags::empty();
         new_tags.insert("highway", "footway");
         new_tags.insert("footway", "sidewalk");
 
-        info!(
-            "Splitting {} new sidewalks into smaller chunks aligned to roads",
-            raw_new_sidewalks.len()
-        );
-        let closest_road = RTree::bulk_load(roads_with_ways);
         let mut new_sidewalks = Vec::new();
         let mut roads_with_new_left = HashSet::new();
         let mut roads_with_new_right = HashSet::new();
-        for sidewalk in raw_new_sidewalks {
-            for (ls, way, side) in split_new_sidewalks(sidewalk, &closest_road) {
-                if self.derived_ways[&way].kind == Kind::RoadWithSeparate {
-                    // If there are already separate sidewalks on this side, skip
-                    if !self.derived_ways[&way].tags.is("sidewalk:right", "yes")
-                        && side == Side::Right
-                    {
-                        continue;
-                    }
-                    if !self.derived_ways[&way].tags.is("sidewalk:left", "yes")
-                        && side == Side::Left
-                    {
-                        continue;
-                    }
-                } else {
-                    // If the road lacks sidewalks on this side, skip
-                    if !side_is_allowed_by_non_separate_tags(&self.derived_ways[&way].tags, side) {
-                        continue;
-                    }
-                }
-
-                let mut tags = new_tags.clone();
-                tags.insert("tmp:closest_way", way.0.to_string());
-                tags.insert("tmp:osm_way_id", format!("way/{}", way.0));
-                tags.insert("tmp:side", format!("{side:?}"));
-                new_sidewalks.push((ls, tags));
+        for (ls, ways, side) in pieces {
+            let way = ways[0];
+            let road = &self.derived_ways[&way];
+            let mut tags = new_tags.clone();
+            inherit_tags(
+                &options.inherit_tags,
+                &road.tags,
+                side,
+                runs_against(&ls, &road.linestring),
+                &mut tags,
+            );
+            let provenance = Provenance {
+                osm_node: None,
+                osm_way: Some(way),
+                side: Some(side),
+            };
+            new_sidewalks.push((ls, tags, provenance));
 
-                if side == Side::Left {
-                    roads_with_new_left.insert(way);
-                }
-                if side == Side::Right {
-                    roads_with_new_right.insert(way);
-                }
+            if side == Side::Left {
+                roads_with_new_left.extend(ways);
+            } else {
+                roads_with_new_right.extend(ways);
             }
         }
 
@@ -173,7 +425,7 @@ impl Speedwalk {
             new_sidewalks.len()
         );
         let mut insert_new_nodes = HashMap::new();
-        for (new_sidewalk, _) in &mut new_sidewalks {
+        for (new_sidewalk, _, _) in &mut new_sidewalks {
             let bbox = aabb(new_sidewalk);
             for obj in closest_way.locate_in_envelope_intersecting(&bbox) {
                 for (pt, idx1, _) in find_all_intersections(new_sidewalk, obj.geom()) {
@@ -247,6 +499,255 @@ impl Speedwalk {
             modify_existing_way_tags,
         }
     }
+
+    fn buffer_all_roads(
+        &self,
+        roads: MultiLineString,
+        closest_road: &RTree<GeomWithData<LineString, WayID>>,
+        options: &SidewalkOptions,
+    ) -> Vec<(LineString, Vec<WayID>, Side)> {
+        // Buffer these all in one batch; it's much cleaner
+        info!("Creating one big buffered blob");
+        let subtract_polygons = roads.buffer_with_style(
+            BufferStyle::new(BUFFER_DISTANCE).line_join(LineJoin::Round(BUFFER_DISTANCE)),
+        );
+
+        // Debugging
+        #[cfg(target_arch = "wasm32")]
+        if false {
+            utils::download_string(
+                &serde_json::to_string(&self.mercator.to_wgs84_gj(&subtract_polygons)).unwrap(),
+                "buffered.geojson",
+            )
+            .unwrap();
+        }
+
+        let mut raw_new_sidewalks = Vec::new();
+        for polygon in subtract_polygons {
+            let (exterior, holes) = polygon.into_inner();
+            raw_new_sidewalks.push(exterior);
+            raw_new_sidewalks.extend(holes);
+        }
+
+        info!(
+            "Splitting {} new sidewalks into smaller chunks aligned to roads",
+            raw_new_sidewalks.len()
+        );
+        raw_new_sidewalks
+            .into_iter()
+            .flat_map(|sidewalk| self.split_new_sidewalks(sidewalk, closest_road, options))
+            .collect()
+    }
+
+    fn offset_each_road(
+        &self,
+        closest_road: &RTree<GeomWithData<LineString, WayID>>,
+        options: &SidewalkOptions,
+    ) -> Vec<(LineString, Vec<WayID>, Side)> {
+        // How many of the roads getting sidewalks use each node?
+        let mut road_count: HashMap<NodeID, usize> = HashMap::new();
+        for obj in closest_road.iter() {
+            for node in &self.derived_ways[&obj.data].node_ids {
+                *road_count.entry(*node).or_insert(0) += 1;
+            }
+        }
+
+        info!("Offsetting {} roads", closest_road.size());
+        let mut pieces: Vec<(LineString, WayID, Side)> = Vec::new();
+        for obj in closest_road.iter() {
+            let road = &self.derived_ways[&obj.data];
+            for side in [Side::Left, Side::Right] {
+                if !side_tagged_yes(&road.tags, side) {
+                    continue;
+                }
+                let Some(offset) = road.linestring.offset_curve(match side {
+                    Side::Left => -BUFFER_DISTANCE,
+                    Side::Right => BUFFER_DISTANCE,
+                }) else {
+                    continue;
+                };
+
+                // Trim the sidewalk wherever it gets close to another road, so it ends at the
+                // corner with that road's sidewalk. Roads that just continue this one don't count.
+                let others: Vec<LineString> = closest_road
+                    .locate_in_envelope_intersecting(&buffer_aabb(aabb(&offset), BUFFER_DISTANCE))
+                    .filter(|other| {
+                        other.data != obj.data
+                            && !self.continues_road(obj.data, other.data, &road_count)
+                    })
+                    .map(|other| other.geom().clone())
+                    .collect();
+                let trimmed = if others.is_empty() {
+                    MultiLineString(vec![offset])
+                } else {
+                    MultiLineString(others)
+                        .buffer(TRIM_DISTANCE)
+                        .clip(&MultiLineString(vec![offset]), true)
+                };
+
+                for ls in trimmed {
+                    if Euclidean.length(&ls) >= JOIN_ENDPOINTS_DISTANCE {
+                        pieces.push((ls, obj.data, side));
+                    }
+                }
+            }
+        }
+
+        join_nearby_endpoints(&mut pieces);
+
+        let mut output = Vec::new();
+        for (ls, way, side) in pieces {
+            let lines = ls.lines().map(|line| (line, way, side)).collect();
+            output.extend(self.chunk_lines(lines, options));
+        }
+        output
+    }
+
+    /// Does `other` just continue `road`, meeting at the end of both without any other roads?
+    fn continues_road(
+        &self,
+        road: WayID,
+        other: WayID,
+        road_count: &HashMap<NodeID, usize>,
+    ) -> bool {
+        let endpoints = |way: WayID| {
+            let nodes = &self.derived_ways[&way].node_ids;
+            [nodes[0], *nodes.last().unwrap()]
+        };
+        let other_endpoints = endpoints(other);
+        endpoints(road)
+            .into_iter()
+            .any(|n| other_endpoints.contains(&n) && road_count[&n] == 2)
+    }
+
+    // For each line, find the closest road that contributed to it and guess which side of the
+    // road it's on. Chunk by that (or by finer/coarser keys, depending on options). Returns every
+    // road along each chunk, with the one covering the most length first.
+    fn split_new_sidewalks(
+        &self,
+        full: LineString,
+        rtree: &RTree<GeomWithData<LineString, WayID>>,
+        options: &SidewalkOptions,
+    ) -> Vec<(LineString, Vec<WayID>, Side)> {
+        let mut lines: Vec<(Line, WayID, Side)> = Vec::new();
+        for line in full.lines() {
+            let midpt = line.point_at_ratio_from_start(&Euclidean, 0.5);
+            let road = rtree
+                .nearest_neighbor(&midpt)
+                .expect("no closest road to a new sidewalk line");
+            let side = classify_side(midpt, road);
+
+            lines.push((line, road.data, side));
+        }
+        smooth_stray_lines(&mut lines, options.max_stray_segments);
+
+        self.chunk_lines(lines, options)
+    }
+
+    fn chunk_lines(
+        &self,
+        lines: Vec<(Line, WayID, Side)>,
+        options: &SidewalkOptions,
+    ) -> Vec<(LineString, Vec<WayID>, Side)> {
+        let keyed_lines: Vec<(Line, WayID, Side, Option<ChunkKey>)> = lines
+            .into_iter()
+            .map(|(line, way, side)| {
+                // Lines on a side of the road that shouldn't get a sidewalk are dropped
+                let key = self
+                    .side_gets_sidewalk(way, side)
+                    .then(|| match options.split_at {
+                        SplitAt::RoadChanges => ChunkKey::Road(way, side),
+                        SplitAt::Junctions => {
+                            ChunkKey::RoadSection(way, side, self.road_section(way, &line))
+                        }
+                        SplitAt::AttributeChanges => {
+                            let road = &self.derived_ways[&way];
+                            let mut tags = Tags::empty();
+                            inherit_tags(
+                                &options.inherit_tags,
+                                &road.tags,
+                                side,
+                                runs_against(&line.into(), &road.linestring),
+                                &mut tags,
+                            );
+                            ChunkKey::Attributes(side, tags.0)
+                        }
+                    });
+                (line, way, side, key)
+            })
+            .collect();
+
+        let mut output = Vec::new();
+        for chunk in keyed_lines.chunk_by(|a, b| a.3 == b.3) {
+            if chunk[0].3.is_none() {
+                continue;
+            }
+
+            // Combine the lines
+            let mut pts = vec![chunk[0].0.start];
+            let side = chunk[0].2;
+            let mut roads: Vec<(WayID, f64)> = Vec::new();
+            for (line, road, _, _) in chunk {
+                pts.push(line.end);
+                let length = Euclidean.length(line);
+                if let Some(pair) = roads.iter_mut().find(|(r, _)| r == road) {
+                    pair.1 += length;
+                } else {
+                    roads.push((*road, length));
+                }
+            }
+            roads.sort_by(|a, b| b.1.total_cmp(&a.1));
+            let roads: Vec<WayID> = roads.into_iter().map(|(r, _)| r).collect();
+
+            let ls = LineString::new(pts);
+            if let Some(max_length) = options.max_length_meters {
+                for piece in split_by_length(ls, max_length) {
+                    output.push((piece, roads.clone(), side));
+                }
+            } else {
+                output.push((ls, roads, side));
+            }
+        }
+        output
+    }
+
+    fn side_gets_sidewalk(&self, way: WayID, side: Side) -> bool {
+        let road = &self.derived_ways[&way];
+        if road.kind == Kind::RoadWithSeparate {
+            // If there are already separate sidewalks on this side, skip
+            road.tags.is(&format!("sidewalk:{}", side.key()), "yes")
+        } else {
+            // If the road lacks sidewalks on this side, skip
+            side_is_allowed_by_non_separate_tags(&road.tags, side)
+        }
+    }
+
+    /// Numbers the pieces of a road between junctions with other roads, and returns the piece
+    /// closest to `line`.
+    fn road_section(&self, way: WayID, line: &Line) -> usize {
+        let road = &self.derived_ways[&way];
+        let midpt = line.point_at_ratio_from_start(&Euclidean, 0.5);
+        let Some((closest_idx, _)) = road
+            .linestring
+            .lines()
+            .enumerate()
+            .min_by_key(|(_, road_line)| (Euclidean.distance(road_line, &midpt) * 10e6) as usize)
+        else {
+            return 0;
+        };
+        // Line i goes from node i to node i + 1
+        road.node_ids[1..=closest_idx]
+            .iter()
+            .filter(|n| {
+                self.derived_nodes[n]
+                    .way_ids
+                    .iter()
+                    .filter(|w| self.derived_ways[w].kind.is_road())
+                    .count()
+                    > 1
+            })
+            .count()
+    }
 }
 
 /// Returns all points of intersection between ls1 and ls2, and the index to insert those points
@@ -272,39 +773,145 @@ fn find_all_intersections(ls1: &LineString, ls2: &LineString) -> Vec<(Coord, usi
     hits
 }
 
-// For each point, find the closest road road that contributed to it. Chunk by that, including
-// the guess on which side of the road.
-fn split_new_sidewalks(
-    full: LineString,
-    rtree: &RTree<GeomWithData<LineString, WayID>>,
-) -> Vec<(LineString, WayID, Side)> {
-    let mut lines: Vec<(Line, WayID, Side)> = Vec::new();
-    for line in full.lines() {
-        let midpt = line.point_at_ratio_from_start(&Euclidean, 0.5);
-        let road = rtree
-            .nearest_neighbor(&midpt)
-            .expect("no closest road to a new sidewalk line");
-        let side = classify_side(midpt, road);
+/// Is there explicitly a sidewalk on this side of the road?
+pub(crate) fn side_tagged_yes(tags: &Tags, side: Side) -> bool {
+    tags.is_any("sidewalk", vec!["both", "yes", side.key()])
+        || tags.is("sidewalk:both", "yes")
+        || tags.is(&format!("sidewalk:{}", side.key()), "yes")
+}
 
-        lines.push((line, road.data, side));
+/// The sidewalk value for one side of the road, from `sidewalk:<side>`, `sidewalk:both` or
+/// `sidewalk`. Values for both sides are normalized to yes or no.
+pub(crate) fn side_sidewalk_tag(tags: &Tags, side: Side) -> Option<&str> {
+    if let Some(value) = tags.get(&format!("sidewalk:{}", side.key())) {
+        return Some(value);
+    }
+    if let Some(value) = tags.get("sidewalk:both") {
+        return Some(value);
+    }
+    match tags.get("sidewalk")?.as_str() {
+        "both" | "yes" => Some("yes"),
+        "no" | "none" => Some("no"),
+        x if x == side.key() => Some("yes"),
+        "left" | "right" => Some("no"),
+        // sidewalk=separate doesn't say which side
+        _ => None,
     }
+}
+
+/// Pieces of sidewalk generated separately for different roads should meet at corners, but
+/// trimming leaves small gaps. Move endpoints close to each other to the same spot.
+fn join_nearby_endpoints(pieces: &mut [(LineString, WayID, Side)]) {
+    let endpoints: Vec<GeomWithData<Point, (usize, bool)>> = pieces
+        .iter()
+        .enumerate()
+        .flat_map(|(idx, (ls, _, _))| {
+            [
+                GeomWithData::new(Point::from(ls.0[0]), (idx, true)),
+                GeomWithData::new(Point::from(*ls.0.last().unwrap()), (idx, false)),
+            ]
+        })
+        .collect();
+    let rtree = RTree::bulk_load(endpoints.clone());
+
+    let mut done = HashSet::new();
+    for endpoint in &endpoints {
+        if done.contains(&endpoint.data) {
+            continue;
+        }
+        let group: Vec<_> = rtree
+            .locate_within_distance(
+                *endpoint.geom(),
+                JOIN_ENDPOINTS_DISTANCE * JOIN_ENDPOINTS_DISTANCE,
+            )
+            .filter(|other| !done.contains(&other.data))
+            .collect();
+        let n = group.len() as f64;
+        let joined = Coord {
+            x: group.iter().map(|pt| pt.geom().x()).sum::<f64>() / n,
+            y: group.iter().map(|pt| pt.geom().y()).sum::<f64>() / n,
+        };
+        for pt in group {
+            let (idx, first) = pt.data;
+            let pts = &mut pieces[idx].0.0;
+            if first {
+                pts[0] = joined;
+            } else {
+                *pts.last_mut().unwrap() = joined;
+            }
+            done.insert(pt.data);
+        }
+    }
+}
+
+/// Where a run of up to `max_stray` lines is matched to a different road or side than the lines
+/// on both sides of it, match the run to that road and side too.
+fn smooth_stray_lines(lines: &mut [(Line, WayID, Side)], max_stray: usize) {
+    if max_stray == 0 {
+        return;
+    }
+
+    // (start index, length)
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    let mut start = 0;
+    for idx in 1..=lines.len() {
+        if idx == lines.len() || (lines[idx].1, lines[idx].2) != (lines[start].1, lines[start].2) {
+            runs.push((start, idx - start));
+            start = idx;
+        }
+    }
+
+    for pair in runs.windows(3) {
+        let (before, (start, len), (after, _)) = (pair[0].0, pair[1], pair[2]);
+        let key = (lines[before].1, lines[before].2);
+        if len <= max_stray && key == (lines[after].1, lines[after].2) {
+            for line in &mut lines[start..start + len] {
+                (line.1, line.2) = key;
+            }
+        }
+    }
+}
+
+/// Split a LineString evenly into the fewest pieces no longer than `max_length`.
+fn split_by_length(ls: LineString, max_length: f64) -> Vec<LineString> {
+    let total = Euclidean.length(&ls);
+    if max_length <= 0.0 || total <= max_length {
+        return vec![ls];
+    }
+    let num_pieces = (total / max_length).ceil() as usize;
+    let piece_length = total / (num_pieces as f64);
 
     let mut output = Vec::new();
-    for chunk in lines.chunk_by(|a, b| (a.1, a.2) == (b.1, b.2)) {
-        // Combine the lines
-        let mut pts = vec![chunk[0].0.start];
-        let road = chunk[0].1;
-        let side = chunk[0].2;
-        for (line, _, _) in chunk {
+    let mut pts = vec![ls.0[0]];
+    let mut length_so_far = 0.0;
+    for line in ls.lines() {
+        let mut start = line.start;
+        let mut remaining = Euclidean.length(&line);
+        // The last piece gets whatever's left, so floating point error can't make an extra tiny
+        // piece
+        while length_so_far + remaining > piece_length && output.len() + 1 < num_pieces {
+            let step = piece_length - length_so_far;
+            let cut: Coord = Euclidean
+                .point_at_distance_between(Point::from(start), Point::from(line.end), step)
+                .into();
+            pts.push(cut);
+            output.push(LineString::new(std::mem::take(&mut pts)));
+            pts.push(cut);
+            remaining -= step;
+            start = cut;
+            length_so_far = 0.0;
+        }
+        length_so_far += remaining;
+        if pts.last() != Some(&line.end) {
             pts.push(line.end);
         }
-        output.push((LineString::new(pts), road, side));
     }
+    output.push(LineString::new(pts));
     output
 }
 
 /// Classify which side of the road a point is on using the tangent at the nearest point on the road.
-fn classify_side(pt: Point, road: &GeomWithData<LineString, WayID>) -> Side {
+pub(crate) fn classify_side(pt: Point, road: &GeomWithData<LineString, WayID>) -> Side {
     let road = road.geom();
     let Some(fraction) = road.line_locate_point(&pt) else {
         return Side::Left;
@@ -400,4 +1007,252 @@ mod tests {
             Side::Right
         ));
     }
+
+    fn count_new_sidewalks(osm: &str, split_at: SplitAt) -> usize {
+        let model = Speedwalk::new_from_osm(osm.as_bytes(), None).unwrap();
+        let options = SidewalkOptions {
+            split_at,
+            ..Default::default()
+        };
+        model.make_all_sidewalks(false, &options).new_ways.len()
+    }
+
+    #[test]
+    fn test_split_at_junctions() {
+        // A side road joins the middle of a longer road
+        let osm = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <node id="1" lon="0.000000" lat="0.000000" version="1" />
+  <node id="2" lon="0.000900" lat="0.000000" version="1" />
+  <node id="3" lon="0.001800" lat="0.000000" version="1" />
+  <node id="4" lon="0.000900" lat="0.000900" version="1" />
+  <way id="100" version="1">
+    <nd ref="1"/><nd ref="2"/><nd ref="3"/>
+    <tag k="highway" v="residential"/>
+    <tag k="sidewalk" v="both"/>
+  </way>
+  <way id="101" version="1">
+    <nd ref="2"/><nd ref="4"/>
+    <tag k="highway" v="residential"/>
+    <tag k="sidewalk" v="no"/>
+  </way>
+</osm>"#;
+        assert!(
+            count_new_sidewalks(osm, SplitAt::Junctions)
+                > count_new_sidewalks(osm, SplitAt::RoadChanges)
+        );
+    }
+
+    #[test]
+    fn test_split_at_attribute_changes() {
+        // One road split into two ways. When the two ways have the same attributes, splitting
+        // by attributes ignores the change in ways.
+        let osm = |lit: &str| {
+            format!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <node id="1" lon="0.000000" lat="0.000000" version="1" />
+  <node id="2" lon="0.000900" lat="0.000000" version="1" />
+  <node id="3" lon="0.001800" lat="0.000000" version="1" />
+  <way id="100" version="1">
+    <nd ref="1"/><nd ref="2"/>
+    <tag k="highway" v="residential"/>
+    <tag k="sidewalk" v="both"/>
+    <tag k="lit" v="yes"/>
+  </way>
+  <way id="101" version="1">
+    <nd ref="2"/><nd ref="3"/>
+    <tag k="highway" v="residential"/>
+    <tag k="sidewalk" v="both"/>
+    <tag k="lit" v="{lit}"/>
+  </way>
+</osm>"#
+            )
+        };
+        assert!(
+            count_new_sidewalks(&osm("yes"), SplitAt::AttributeChanges)
+                < count_new_sidewalks(&osm("yes"), SplitAt::RoadChanges)
+        );
+        assert_eq!(
+            count_new_sidewalks(&osm("no"), SplitAt::AttributeChanges),
+            count_new_sidewalks(&osm("no"), SplitAt::RoadChanges)
+        );
+    }
+
+    #[test]
+    fn test_offset_each_road() {
+        // A side road going north from the middle of an east-west road. Only the west side of the
+        // side road is tagged.
+        let osm = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <node id="1" lon="0.000000" lat="0.000000" version="1" />
+  <node id="2" lon="0.000900" lat="0.000000" version="1" />
+  <node id="3" lon="0.001800" lat="0.000000" version="1" />
+  <node id="4" lon="0.000900" lat="0.000900" version="1" />
+  <way id="100" version="1">
+    <nd ref="1"/><nd ref="2"/><nd ref="3"/>
+    <tag k="highway" v="residential"/>
+    <tag k="sidewalk" v="both"/>
+  </way>
+  <way id="101" version="1">
+    <nd ref="2"/><nd ref="4"/>
+    <tag k="highway" v="primary"/>
+    <tag k="lanes" v="4"/>
+    <tag k="sidewalk:left" v="yes"/>
+    <tag k="sidewalk:right" v="no"/>
+  </way>
+</osm>"#;
+        let model = Speedwalk::new_from_osm(osm.as_bytes(), None).unwrap();
+        let options = SidewalkOptions {
+            method: GenerationMethod::OffsetEachRoad,
+            ..Default::default()
+        };
+        let mut sides: Vec<(i64, Side)> = model
+            .make_all_sidewalks(false, &options)
+            .new_ways
+            .into_iter()
+            .map(|(_, _, provenance)| (provenance.osm_way.unwrap().0, provenance.side.unwrap()))
+            .collect();
+        sides.sort_by_key(|(way, side)| (*way, side.key()));
+        // The north side of the main road is split by the side road
+        assert_eq!(
+            sides,
+            vec![
+                (100, Side::Left),
+                (100, Side::Left),
+                (100, Side::Right),
+                (101, Side::Left)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_make_selected_sidewalks() {
+        let osm = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <node id="1" lon="0.000000" lat="0.000000" version="1" />
+  <node id="2" lon="0.000900" lat="0.000000" version="1" />
+  <way id="100" version="1">
+    <nd ref="1"/><nd ref="2"/>
+    <tag k="highway" v="residential"/>
+    <tag k="sidewalk" v="both"/>
+  </way>
+</osm>"#;
+        let model = Speedwalk::new_from_osm(osm.as_bytes(), None).unwrap();
+        let options = SidewalkOptions::default();
+        let all = model.make_all_sidewalks(false, &options);
+        assert_eq!(all.new_ways.len(), 2);
+
+        let preview: geojson::FeatureCollection = model
+            .preview_make_all_sidewalks(false, &options)
+            .unwrap()
+            .parse()
+            .unwrap();
+        let new_ways = preview
+            .features
+            .iter()
+            .filter(|f| f.property("preview").unwrap() == "new_way")
+            .count();
+        assert_eq!(new_ways, 2);
+
+        // Only keeping the second sidewalk leaves the other side of the road alone
+        let some = model.make_selected_sidewalks(false, &options, &[1]);
+        assert_eq!(some.new_ways.len(), 1);
+        assert_eq!(some.new_ways[0].2.side, all.new_ways[1].2.side);
+        let mut set_tags = Vec::new();
+        for cmd in &some.modify_existing_way_tags[&WayID(100)] {
+            if let TagCmd::Set(k, v) = cmd {
+                set_tags.push(format!("{k}={v}"));
+            }
+        }
+        assert!(
+            set_tags.contains(&"sidewalk:left=separate".to_string())
+                != set_tags.contains(&"sidewalk:right=separate".to_string())
+        );
+    }
+
+    #[test]
+    fn test_smooth_stray_lines() {
+        let line = Line::new(Coord { x: 0.0, y: 0.0 }, Coord { x: 1.0, y: 0.0 });
+        let mut lines = vec![
+            (line, WayID(1), Side::Left),
+            (line, WayID(1), Side::Left),
+            (line, WayID(2), Side::Left),
+            (line, WayID(1), Side::Left),
+            (line, WayID(3), Side::Right),
+            (line, WayID(3), Side::Right),
+        ];
+        smooth_stray_lines(&mut lines, 1);
+        let ways: Vec<i64> = lines.iter().map(|(_, w, _)| w.0).collect();
+        // The stray 2 is absorbed, but the single 1 between 2 and 3 isn't a stray
+        assert_eq!(ways, vec![1, 1, 1, 1, 3, 3]);
+    }
+
+    #[test]
+    fn test_split_by_length() {
+        let ls = LineString::new(vec![
+            Coord { x: 0.0, y: 0.0 },
+            Coord { x: 25.0, y: 0.0 },
+            Coord { x: 25.0, y: 5.0 },
+        ]);
+        let pieces = split_by_length(ls.clone(), 10.0);
+        assert_eq!(pieces.len(), 3);
+        for piece in &pieces {
+            assert!((Euclidean.length(piece) - 10.0).abs() < 1e-9);
+        }
+        // Pieces share endpoints and keep the original vertices
+        assert_eq!(pieces[0].0.last(), pieces[1].0.first());
+        assert_eq!(pieces[1].0.last(), pieces[2].0.first());
+        assert!(pieces[2].0.contains(&Coord { x: 25.0, y: 0.0 }));
+        assert_eq!(pieces[2].0.last(), ls.0.last());
+
+        assert_eq!(split_by_length(ls, 100.0).len(), 1);
+    }
+
+    #[test]
+    fn test_inherit_tags_prefers_side_specific_values() {
+        let road = Tags::new_from_pairs(&vec![
+            "name=High Street",
+            "lit=yes",
+            "sidewalk:left:surface=paving_stones",
+            "sidewalk:both:surface=asphalt",
+            "sidewalk:width=2",
+            "sidewalk:right:kerb=lowered",
+        ]);
+        let mapping = default_inherit_tags();
+
+        let mut left = Tags::empty();
+        inherit_tags(&mapping, &road, Side::Left, false, &mut left);
+        assert_eq!(left.get("surface").unwrap(), "paving_stones");
+        assert_eq!(left.get("width").unwrap(), "2");
+        assert_eq!(left.get("lit").unwrap(), "yes");
+        assert_eq!(left.get("street:name").unwrap(), "High Street");
+        assert!(!left.has("kerb"));
+        assert!(!left.has("name"));
+
+        let mut right = Tags::empty();
+        inherit_tags(&mapping, &road, Side::Right, false, &mut right);
+        assert_eq!(right.get("surface").unwrap(), "asphalt");
+        assert_eq!(right.get("kerb").unwrap(), "lowered");
+    }
+
+    #[test]
+    fn test_inherit_tags_flips_incline_on_reversed_sidewalks() {
+        let road = Tags::new_from_pairs(&vec![
+            "sidewalk:both:incline=5%",
+            "sidewalk:left:incline=up",
+        ]);
+        let mapping = default_inherit_tags();
+
+        let mut right = Tags::empty();
+        inherit_tags(&mapping, &road, Side::Right, true, &mut right);
+        assert_eq!(right.get("incline").unwrap(), "-5%");
+
+        let mut left = Tags::empty();
+        inherit_tags(&mapping, &road, Side::Left, true, &mut left);
+        assert_eq!(left.get("incline").unwrap(), "down");
+
+        assert_eq!(reverse_incline("-3°"), "3°");
+        assert_eq!(reverse_incline("yes"), "yes");
+    }
 }
@@ -0,0 +1,196 @@
+use geo::{Closest, ClosestPoint, Coord, Distance, Euclidean, Intersects, Line, Point};
+use osm_reader::{NodeID, WayID};
+use rstar::{Envelope, RTree, primitives::GeomWithData};
+use utils::{aabb, buffer_aabb};
+
+use crate::{Kind, Speedwalk, Way, problems::ProblemScope};
+
+/// Dangling endpoints this close to another walkable way probably should connect to it
+pub(crate) const MAX_NEAR_MISS_GAP: f64 = 3.0;
+
+/// A dangling endpoint of a walkable way, close to another one
+pub(crate) struct NearMiss {
+    pub way: WayID,
+    pub node: NodeID,
+    pub target: WayID,
+    /// The closest point on the target way
+    pub snapped: Coord,
+    pub distance: f64,
+}
+
+impl Speedwalk {
+    /// Dangling endpoints of sidewalks, crossings and other walkable ways in scope that nearly
+    /// reach another walkable way, with no building or road in between. Sorted by way.
+    pub(crate) fn find_near_misses(&self, scope: Option<&ProblemScope>) -> Vec<NearMiss> {
+        let ways: Vec<WayID> = self
+            .derived_ways
+            .iter()
+            .filter(|(_, way)| is_walkable(way) && scope.is_none_or(|s| s.has_way(way)))
+            .map(|(id, _)| *id)
+            .collect();
+        self.near_misses(ways)
+    }
+
+    /// Near misses from either end of one way
+    pub(crate) fn near_misses_on(&self, way: WayID) -> Vec<NearMiss> {
+        self.near_misses(vec![way])
+    }
+
+    fn near_misses(&self, mut ways: Vec<WayID>) -> Vec<NearMiss> {
+        ways.sort();
+        let Some(search) = ways
+            .iter()
+            .map(|id| buffer_aabb(aabb(&self.derived_ways[id].linestring), MAX_NEAR_MISS_GAP))
+            .reduce(|a, b| a.merged(&b))
+        else {
+            return Vec::new();
+        };
+        let nearby_ways = || {
+            self.derived_ways
+                .iter()
+                .filter(move |(_, way)| search.intersects(&aabb(&way.linestring)))
+        };
+        let closest_walkable = RTree::bulk_load(
+            nearby_ways()
+                .filter(|(_, way)| is_walkable(way))
+                .map(|(id, way)| GeomWithData::new(way.linestring.clone(), *id))
+                .collect(),
+        );
+        let closest_road = RTree::bulk_load(
+            nearby_ways()
+                .filter(|(_, way)| way.kind.is_road())
+                .map(|(id, way)| GeomWithData::new(way.linestring.clone(), *id))
+                .collect(),
+        );
+
+        let mut results = Vec::new();
+        for way_id in ways {
+            let way = &self.derived_ways[&way_id];
+            let mut endpoints = vec![way.node_ids[0], *way.node_ids.last().unwrap()];
+            // Loops don't have a dangling end
+            endpoints.dedup();
+
+            for node_id in endpoints {
+                let node = &self.derived_nodes[&node_id];
+                if node.way_ids.len() > 1 {
+                    continue;
+                }
+
+                let mut best: Option<(WayID, Coord, f64)> = None;
+                for obj in closest_walkable.locate_within_distance(
+                    Point::from(node.pt),
+                    MAX_NEAR_MISS_GAP * MAX_NEAR_MISS_GAP,
+                ) {
+                    if obj.data == way_id {
+                        continue;
+                    }
+                    let snapped: Coord = match obj.geom().closest_point(&Point::from(node.pt)) {
+                        Closest::Intersection(pt) | Closest::SinglePoint(pt) => pt.into(),
+                        Closest::Indeterminate => continue,
+                    };
+                    let distance = Euclidean.distance(node.pt, snapped);
+                    if best.is_some_and(|(_, _, d)| d <= distance) {
+                        continue;
+                    }
+
+                    // Don't connect across roads or through buildings
+                    let gap = Line::new(node.pt, snapped);
+                    if closest_road
+                        .locate_in_envelope_intersecting(&aabb(&gap))
+                        .any(|road| road.geom().intersects(&gap))
+                        || self
+                            .closest_building
+                            .locate_in_envelope_intersecting(&aabb(&gap))
+                            .any(|polygon| polygon.intersects(&gap))
+                    {
+                        continue;
+                    }
+                    best = Some((obj.data, snapped, distance));
+                }
+
+                if let Some((target, snapped, distance)) = best {
+                    results.push(NearMiss {
+                        way: way_id,
+                        node: node_id,
+                        target,
+                        snapped,
+                        distance,
+                    });
+                }
+            }
+        }
+        results
+    }
+}
+
+fn is_walkable(way: &Way) -> bool {
+    matches!(way.kind, Kind::Sidewalk | Kind::Crossing) || way.is_walkable_other()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ProblemType, UserCmd};
+
+    #[test]
+    fn test_near_misses() {
+        // 201 stops 2m short of 200. 202 also does, but there's a road in between.
+        let osm = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <node id="1" lon="0.000000" lat="0.000000" version="1" />
+  <node id="2" lon="0.000900" lat="0.000000" version="1" />
+  <node id="3" lon="0.000200" lat="0.000018" version="1" />
+  <node id="4" lon="0.000200" lat="0.000300" version="1" />
+  <node id="5" lon="0.000700" lat="0.000022" version="1" />
+  <node id="6" lon="0.000700" lat="0.000300" version="1" />
+  <node id="7" lon="0.000500" lat="0.000010" version="1" />
+  <node id="8" lon="0.000900" lat="0.000010" version="1" />
+  <way id="100" version="1">
+    <nd ref="7"/><nd ref="8"/>
+    <tag k="highway" v="service"/>
+  </way>
+  <way id="200" version="1">
+    <nd ref="1"/><nd ref="2"/>
+    <tag k="highway" v="footway"/>
+    <tag k="footway" v="sidewalk"/>
+  </way>
+  <way id="201" version="1">
+    <nd ref="3"/><nd ref="4"/>
+    <tag k="highway" v="footway"/>
+    <tag k="footway" v="sidewalk"/>
+  </way>
+  <way id="202" version="1">
+    <nd ref="5"/><nd ref="6"/>
+    <tag k="highway" v="footway"/>
+    <tag k="footway" v="sidewalk"/>
+  </way>
+</osm>"#;
+        let mut model = Speedwalk::new_from_osm(osm.as_bytes(), None).unwrap();
+        let found = model.find_near_misses(None);
+        assert_eq!(found.len(), 1);
+        assert_eq!(
+            (found[0].way, found[0].node, found[0].target),
+            (WayID(201), NodeID(3), WayID(200))
+        );
+        assert!((found[0].distance - 2.0).abs() < 0.1);
+        let has_problem = |model: &Speedwalk, way: i64| {
+            model.derived_ways[&WayID(way)]
+                .problems
+                .iter()
+                .any(|p| p.problem_type == ProblemType::NearMissEndpoint)
+        };
+        assert!(has_problem(&model, 201));
+        assert!(!has_problem(&model, 202));
+
+        let mut edits = model.take_edits();
+        edits
+            .apply_cmd(UserCmd::SnapEndpoints(WayID(201)), &model)
+            .unwrap();
+        model.set_edits(edits);
+        model.after_edit();
+
+        assert!(!has_problem(&model, 201));
+        let joined = model.derived_ways[&WayID(201)].node_ids[0];
+        assert!(model.derived_ways[&WayID(200)].node_ids.contains(&joined));
+    }
+}
@@ -1,17 +1,562 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashMap};
 
+use anyhow::Result;
 use geo::{Euclidean, InterpolatableLine, Intersects, Length, LineLocatePoint, LineString, Point};
 use geojson::Feature;
-use osm_reader::WayID;
-use rstar::{RTree, primitives::GeomWithData};
-use utils::{LineSplit, aabb, buffer_aabb};
+use osm_reader::{NodeID, WayID};
+use rstar::{AABB, Envelope, RTree, primitives::GeomWithData};
+use serde::{Deserialize, Serialize, Serializer, ser::SerializeStruct};
+use utils::{LineSplit, OffsetCurve, aabb, buffer_aabb};
 
-use crate::{Kind, Problem, Speedwalk};
+use crate::{
+    Kind, Node, Problem, Side, Speedwalk, UserCmd, Way,
+    accessibility::Crossing,
+    crossing_tags::deprecated_crossing_tags,
+    duplicate_sidewalks::DuplicateFix,
+    make_sidewalks::{classify_side, side_sidewalk_tag},
+};
+
+/// Every kind of problem that can be detected. The codes are stable; the wording of descriptions
+/// and fixes may change.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ProblemType {
+    MissingCrossingNode,
+    MissingFootwayCrossing,
+    PossibleSeparateSidewalk,
+    AmbiguousSidewalkSeparate,
+    IncompleteSidewalkSides,
+    SeparateSidewalksNotContinued,
+    CrossingNoSidewalk,
+    CrossingBlockedByRoad,
+    CrossingBlockedByBuilding,
+    CrossingEndpointsIdentical,
+    CrossingAtRoadJunction,
+    FootwayCrossesRoadWithoutNode,
+    DuplicateSidewalk,
+    NearMissEndpoint,
+    DeprecatedCrossingTags,
+    InconsistentCrossingTags,
+    SidewalkTaggedNoButMapped,
+    SeparateSidewalkNotFound,
+    CrossingMissingKerb,
+    CrossingMissingTactilePaving,
+    CrossingMissingMarkings,
+    CrossingConnectsViaSteps,
+}
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Category {
+    Tagging,
+    Topology,
+    Geometry,
+    /// Only checked when turned on
+    Accessibility,
+}
+
+/// Everything about a problem type, for listing them all
+#[derive(Serialize)]
+pub struct ProblemTypeInfo {
+    code: &'static str,
+    severity: Severity,
+    category: Category,
+    description: &'static str,
+    fix: &'static str,
+}
+
+impl ProblemType {
+    pub fn all() -> Vec<Self> {
+        vec![
+            Self::MissingCrossingNode,
+            Self::MissingFootwayCrossing,
+            Self::PossibleSeparateSidewalk,
+            Self::AmbiguousSidewalkSeparate,
+            Self::IncompleteSidewalkSides,
+            Self::SeparateSidewalksNotContinued,
+            Self::CrossingNoSidewalk,
+            Self::CrossingBlockedByRoad,
+            Self::CrossingBlockedByBuilding,
+            Self::CrossingEndpointsIdentical,
+            Self::CrossingAtRoadJunction,
+            Self::FootwayCrossesRoadWithoutNode,
+            Self::DuplicateSidewalk,
+            Self::NearMissEndpoint,
+            Self::DeprecatedCrossingTags,
+            Self::InconsistentCrossingTags,
+            Self::SidewalkTaggedNoButMapped,
+            Self::SeparateSidewalkNotFound,
+            Self::CrossingMissingKerb,
+            Self::CrossingMissingTactilePaving,
+            Self::CrossingMissingMarkings,
+            Self::CrossingConnectsViaSteps,
+        ]
+    }
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::MissingCrossingNode => "missing_crossing_node",
+            Self::MissingFootwayCrossing => "missing_footway_crossing",
+            Self::PossibleSeparateSidewalk => "possible_separate_sidewalk",
+            Self::AmbiguousSidewalkSeparate => "ambiguous_sidewalk_separate",
+            Self::IncompleteSidewalkSides => "incomplete_sidewalk_sides",
+            Self::SeparateSidewalksNotContinued => "separate_sidewalks_not_continued",
+            Self::CrossingNoSidewalk => "crossing_no_sidewalk",
+            Self::CrossingBlockedByRoad => "crossing_blocked_by_road",
+            Self::CrossingBlockedByBuilding => "crossing_blocked_by_building",
+            Self::CrossingEndpointsIdentical => "crossing_endpoints_identical",
+            Self::CrossingAtRoadJunction => "crossing_at_road_junction",
+            Self::FootwayCrossesRoadWithoutNode => "footway_crosses_road_without_node",
+            Self::DuplicateSidewalk => "duplicate_sidewalk",
+            Self::NearMissEndpoint => "near_miss_endpoint",
+            Self::DeprecatedCrossingTags => "deprecated_crossing_tags",
+            Self::InconsistentCrossingTags => "inconsistent_crossing_tags",
+            Self::SidewalkTaggedNoButMapped => "sidewalk_tagged_no_but_mapped",
+            Self::SeparateSidewalkNotFound => "separate_sidewalk_not_found",
+            Self::CrossingMissingKerb => "crossing_missing_kerb",
+            Self::CrossingMissingTactilePaving => "crossing_missing_tactile_paving",
+            Self::CrossingMissingMarkings => "crossing_missing_markings",
+            Self::CrossingConnectsViaSteps => "crossing_connects_via_steps",
+        }
+    }
+
+    pub fn severity(self) -> Severity {
+        match self {
+            Self::MissingCrossingNode | Self::MissingFootwayCrossing => Severity::Error,
+            Self::PossibleSeparateSidewalk
+            | Self::AmbiguousSidewalkSeparate
+            | Self::IncompleteSidewalkSides
+            | Self::SeparateSidewalksNotContinued
+            | Self::CrossingNoSidewalk
+            | Self::CrossingBlockedByRoad
+            | Self::CrossingBlockedByBuilding
+            | Self::FootwayCrossesRoadWithoutNode
+            | Self::DuplicateSidewalk
+            | Self::NearMissEndpoint
+            | Self::InconsistentCrossingTags
+            | Self::SidewalkTaggedNoButMapped
+            | Self::SeparateSidewalkNotFound
+            | Self::CrossingConnectsViaSteps => Severity::Warning,
+            Self::CrossingEndpointsIdentical
+            | Self::CrossingAtRoadJunction
+            | Self::DeprecatedCrossingTags
+            | Self::CrossingMissingKerb
+            | Self::CrossingMissingTactilePaving
+            | Self::CrossingMissingMarkings => Severity::Info,
+        }
+    }
+
+    pub fn category(self) -> Category {
+        match self {
+            Self::MissingCrossingNode
+            | Self::MissingFootwayCrossing
+            | Self::PossibleSeparateSidewalk
+            | Self::AmbiguousSidewalkSeparate
+            | Self::IncompleteSidewalkSides
+            | Self::DeprecatedCrossingTags
+            | Self::InconsistentCrossingTags
+            | Self::SidewalkTaggedNoButMapped
+            | Self::SeparateSidewalkNotFound => Category::Tagging,
+            Self::SeparateSidewalksNotContinued
+            | Self::CrossingNoSidewalk
+            | Self::CrossingAtRoadJunction
+            | Self::FootwayCrossesRoadWithoutNode
+            | Self::NearMissEndpoint => Category::Topology,
+            Self::CrossingBlockedByRoad
+            | Self::CrossingBlockedByBuilding
+            | Self::CrossingEndpointsIdentical
+            | Self::DuplicateSidewalk => Category::Geometry,
+            Self::CrossingMissingKerb
+            | Self::CrossingMissingTactilePaving
+            | Self::CrossingMissingMarkings
+            | Self::CrossingConnectsViaSteps => Category::Accessibility,
+        }
+    }
+
+    /// The default note for problems of this type
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::MissingCrossingNode => "missing crossing node",
+            Self::MissingFootwayCrossing => "missing footway=crossing",
+            Self::PossibleSeparateSidewalk => {
+                "possible separate sidewalk near way without it tagged"
+            }
+            Self::AmbiguousSidewalkSeparate => "sidewalk=separate is ambiguous about the side",
+            Self::IncompleteSidewalkSides => {
+                "sidewalk:left and sidewalk:right should each be tagged as separate or no"
+            }
+            Self::SeparateSidewalksNotContinued => "separate sidewalks should be continued here",
+            Self::CrossingNoSidewalk => "no sidewalk to connect crossing to",
+            Self::CrossingBlockedByRoad => "another road blocks connecting crossing to a sidewalk",
+            Self::CrossingBlockedByBuilding => {
+                "a building blocks connecting crossing to a sidewalk"
+            }
+            Self::CrossingEndpointsIdentical => {
+                "both sides of crossing reach the same point on a sidewalk"
+            }
+            Self::CrossingAtRoadJunction => {
+                "crossing node where another road meets wasn't connected"
+            }
+            Self::FootwayCrossesRoadWithoutNode => "crosses a road without a shared node",
+            Self::DuplicateSidewalk => "overlaps another sidewalk",
+            Self::NearMissEndpoint => "ends just short of another walkable way",
+            Self::DeprecatedCrossingTags => "deprecated crossing tags",
+            Self::InconsistentCrossingTags => "crossing way and node tags disagree",
+            Self::SidewalkTaggedNoButMapped => {
+                "no sidewalk tagged on one side, but a separate one seems to be there"
+            }
+            Self::SeparateSidewalkNotFound => {
+                "separate sidewalk tagged on one side, but none was found there"
+            }
+            Self::CrossingMissingKerb => "crossing has no kerb tagged",
+            Self::CrossingMissingTactilePaving => "crossing has no tactile_paving tagged",
+            Self::CrossingMissingMarkings => "crossing has no crossing:markings tagged",
+            Self::CrossingConnectsViaSteps => "crossing connects to sidewalks via steps",
+        }
+    }
+
+    pub fn fix(self) -> &'static str {
+        match self {
+            Self::MissingCrossingNode => {
+                "Tag the node where the crossing way meets the road with highway=crossing"
+            }
+            Self::MissingFootwayCrossing => {
+                "Split the footway at the crossing node and tag the part across the road footway=crossing"
+            }
+            Self::PossibleSeparateSidewalk => {
+                "Tag the road with sidewalk:left/right=separate if the nearby footway is its sidewalk"
+            }
+            Self::AmbiguousSidewalkSeparate => {
+                "Replace sidewalk=separate with sidewalk:both=separate, or tag each side"
+            }
+            Self::IncompleteSidewalkSides => {
+                "Tag sidewalk:left and sidewalk:right as separate or no"
+            }
+            Self::SeparateSidewalksNotContinued => {
+                "Map separate sidewalks along the rest of the road"
+            }
+            Self::CrossingNoSidewalk => {
+                "Map the missing sidewalk, or draw the crossing way by hand"
+            }
+            Self::CrossingBlockedByRoad | Self::CrossingBlockedByBuilding => {
+                "Draw the crossing way by hand"
+            }
+            Self::CrossingEndpointsIdentical => {
+                "Check the crossing node is on the right road, or draw the crossing way by hand"
+            }
+            Self::CrossingAtRoadJunction => {
+                "Move the crossing node off the junction, or draw the crossing way by hand"
+            }
+            Self::FootwayCrossesRoadWithoutNode => {
+                "Add a node shared by the footway and road, or tag the bridge or tunnel"
+            }
+            Self::DuplicateSidewalk => {
+                "Merge the two sidewalks, or delete the duplicate if one was generated"
+            }
+            Self::NearMissEndpoint => "Connect the end to the nearby way",
+            Self::DeprecatedCrossingTags => {
+                "Replace with crossing=*, crossing:markings=* and crossing:island=*"
+            }
+            Self::InconsistentCrossingTags => {
+                "Check the crossing on the ground, then make the way and node tags match"
+            }
+            Self::SidewalkTaggedNoButMapped => {
+                "Tag that side as separate if the footway is its sidewalk"
+            }
+            Self::SeparateSidewalkNotFound => {
+                "Map the separate sidewalk, or fix the tag for that side"
+            }
+            Self::CrossingMissingKerb => {
+                "Tag kerb=* on the crossing, or on barrier=kerb nodes at its ends"
+            }
+            Self::CrossingMissingTactilePaving => {
+                "Tag tactile_paving=yes or no on the crossing, or on the kerbs at its ends"
+            }
+            Self::CrossingMissingMarkings => "Tag crossing:markings=* on the crossing",
+            Self::CrossingConnectsViaSteps => {
+                "Check for a step-free route, or tag the steps with ramp:wheelchair=*"
+            }
+        }
+    }
+
+    pub fn info(self) -> ProblemTypeInfo {
+        ProblemTypeInfo {
+            code: self.code(),
+            severity: self.severity(),
+            category: self.category(),
+            description: self.description(),
+            fix: self.fix(),
+        }
+    }
+}
+
+/// Problems are flattened into `code`, `severity`, `category` and `fix`
+impl Serialize for ProblemType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("ProblemType", 4)?;
+        s.serialize_field("code", self.code())?;
+        s.serialize_field("severity", &self.severity())?;
+        s.serialize_field("category", &self.category())?;
+        s.serialize_field("fix", self.fix())?;
+        s.end()
+    }
+}
+
+impl Problem {
+    pub fn new(problem_type: ProblemType, details: Vec<Feature>) -> Self {
+        Self {
+            problem_type,
+            note: problem_type.description().to_string(),
+            details,
+            suppressed: false,
+            tag_fix: None,
+        }
+    }
+}
+
+/// A problem on one element that somebody has marked as not an issue
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ProblemSuppression {
+    /// Like `node/123` or `way/456`
+    pub element: String,
+    /// A `ProblemType` code
+    pub code: String,
+}
+
+impl Speedwalk {
+    pub fn suppress_problem(&mut self, suppression: ProblemSuppression) {
+        self.suppressed_problems.insert(suppression);
+        self.mark_suppressed_problems();
+    }
+
+    pub fn unsuppress_problem(&mut self, suppression: &ProblemSuppression) {
+        self.suppressed_problems.remove(suppression);
+        self.mark_suppressed_problems();
+    }
+
+    pub fn export_suppressed_problems(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self.suppressed_problems)?)
+    }
+
+    /// Adds to the existing list
+    pub fn import_suppressed_problems(&mut self, input: &str) -> Result<()> {
+        let list: Vec<ProblemSuppression> = serde_json::from_str(input)?;
+        self.suppressed_problems.extend(list);
+        self.mark_suppressed_problems();
+        Ok(())
+    }
+
+    /// Commands applying the tag fix of every problem with this code, except suppressed ones
+    pub(crate) fn tag_fix_cmds(&self, code: &str) -> Vec<UserCmd> {
+        let find_fix = |problems: &Vec<Problem>| {
+            problems
+                .iter()
+                .find(|p| p.problem_type.code() == code && !p.suppressed)
+                .and_then(|p| p.tag_fix.clone())
+        };
+
+        let mut cmds = Vec::new();
+        let mut nodes: Vec<_> = self.derived_nodes.iter().collect();
+        nodes.sort_by_key(|(id, _)| **id);
+        for (id, node) in nodes {
+            if let Some(fix) = find_fix(&node.problems) {
+                cmds.push(UserCmd::SetNodeTags {
+                    node: *id,
+                    remove_keys: fix.remove_keys,
+                    add_tags: fix.add_tags,
+                });
+            }
+        }
+        let mut ways: Vec<_> = self.derived_ways.iter().collect();
+        ways.sort_by_key(|(id, _)| **id);
+        for (id, way) in ways {
+            if let Some(fix) = find_fix(&way.problems) {
+                cmds.push(UserCmd::SetTags {
+                    way: *id,
+                    remove_keys: fix.remove_keys,
+                    add_tags: fix.add_tags,
+                });
+            }
+        }
+        cmds
+    }
+
+    fn mark_suppressed_problems(&mut self) {
+        let is_suppressed = |element: String, problem: &Problem| {
+            self.suppressed_problems.contains(&ProblemSuppression {
+                element,
+                code: problem.problem_type.code().to_string(),
+            })
+        };
+        for (id, node) in &mut self.derived_nodes {
+            for problem in &mut node.problems {
+                problem.suppressed = is_suppressed(format!("node/{}", id.0), problem);
+            }
+        }
+        for (id, way) in &mut self.derived_ways {
+            for problem in &mut way.problems {
+                problem.suppressed = is_suppressed(format!("way/{}", id.0), problem);
+            }
+        }
+    }
+}
+
+/// How far from a road to look for its separate sidewalks
+const SEPARATE_SIDEWALK_SEARCH: f64 = 15.0;
+
+/// A separate sidewalk that seems to run alongside a road
+struct ParallelSidewalk {
+    side: Side,
+    /// The sidewalk and a line from it to the road
+    details: Vec<Feature>,
+}
+
+/// Every problem is assumed to depend only on things within this distance of the element it's on
+pub(crate) const PROBLEM_RADIUS: f64 = 50.0;
+
+/// The area around elements changed by an edit, where problems need to be recalculated
+pub(crate) struct ProblemScope {
+    envelope: AABB<Point>,
+}
+
+impl ProblemScope {
+    /// Compares derived state before and after an edit. Returns `None` if nothing changed.
+    pub(crate) fn from_changes(
+        old_nodes: &HashMap<NodeID, Node>,
+        old_ways: &HashMap<WayID, Way>,
+        new_nodes: &HashMap<NodeID, Node>,
+        new_ways: &HashMap<WayID, Way>,
+    ) -> Option<Self> {
+        let mut touched: Option<AABB<Point>> = None;
+        let mut touch = |envelope: AABB<Point>| {
+            touched = Some(match touched {
+                Some(x) => x.merged(&envelope),
+                None => envelope,
+            });
+        };
+
+        for (id, node) in new_nodes {
+            match old_nodes.get(id) {
+                Some(old)
+                    if old.pt == node.pt
+                        && old.tags == node.tags
+                        && old.way_ids == node.way_ids => {}
+                Some(old) => {
+                    touch(AABB::from_point(old.pt.into()));
+                    touch(AABB::from_point(node.pt.into()));
+                }
+                None => touch(AABB::from_point(node.pt.into())),
+            }
+        }
+        for (id, node) in old_nodes {
+            if !new_nodes.contains_key(id) {
+                touch(AABB::from_point(node.pt.into()));
+            }
+        }
+
+        for (id, way) in new_ways {
+            match old_ways.get(id) {
+                Some(old)
+                    if old.node_ids == way.node_ids
+                        && old.linestring == way.linestring
+                        && old.tags == way.tags
+                        && old.kind == way.kind => {}
+                Some(old) => {
+                    touch(aabb(&old.linestring));
+                    touch(aabb(&way.linestring));
+                }
+                None => touch(aabb(&way.linestring)),
+            }
+        }
+        for (id, way) in old_ways {
+            if !new_ways.contains_key(id) {
+                touch(aabb(&way.linestring));
+            }
+        }
+
+        Some(Self {
+            envelope: buffer_aabb(touched?, PROBLEM_RADIUS),
+        })
+    }
+
+    pub(crate) fn has_node(&self, node: &Node) -> bool {
+        self.envelope.contains_point(&node.pt.into())
+    }
+
+    pub(crate) fn has_way(&self, way: &Way) -> bool {
+        self.envelope.intersects(&aabb(&way.linestring))
+    }
+
+    /// Is the node within `buffer` of the scope?
+    pub(crate) fn near_node(&self, node: &Node, buffer: f64) -> bool {
+        buffer_aabb(self.envelope, buffer).contains_point(&node.pt.into())
+    }
+
+    /// Is the way within `buffer` of the scope?
+    pub(crate) fn near_way(&self, way: &Way, buffer: f64) -> bool {
+        buffer_aabb(self.envelope, buffer).intersects(&aabb(&way.linestring))
+    }
+}
 
 impl Speedwalk {
     pub fn recalculate_problems(&mut self) {
+        for node in self.derived_nodes.values_mut() {
+            node.problems.clear();
+        }
+        for way in self.derived_ways.values_mut() {
+            way.problems.clear();
+        }
+        self.recalculate_problems_in(None);
+    }
+
+    /// Only recalculate problems near elements that changed since `old_nodes` and `old_ways`,
+    /// copying the rest over
+    pub(crate) fn recalculate_problems_after(
+        &mut self,
+        old_nodes: HashMap<NodeID, Node>,
+        old_ways: HashMap<WayID, Way>,
+    ) {
+        let scope = ProblemScope::from_changes(
+            &old_nodes,
+            &old_ways,
+            &self.derived_nodes,
+            &self.derived_ways,
+        );
+        for (id, old) in old_nodes {
+            if let Some(node) = self.derived_nodes.get_mut(&id)
+                && !scope.as_ref().is_some_and(|s| s.has_node(node))
+            {
+                node.problems = old.problems;
+            }
+        }
+        for (id, old) in old_ways {
+            if let Some(way) = self.derived_ways.get_mut(&id)
+                && !scope.as_ref().is_some_and(|s| s.has_way(way))
+            {
+                way.problems = old.problems;
+            }
+        }
+
+        if let Some(scope) = scope {
+            self.recalculate_problems_in(Some(&scope));
+        } else {
+            self.mark_suppressed_problems();
+        }
+    }
+
+    /// Assumes problems in scope are empty
+    fn recalculate_problems_in(&mut self, scope: Option<&ProblemScope>) {
         let mut problem_nodes = Vec::new();
         let mut problem_ways = Vec::new();
+        let node_in_scope = |node: &Node| scope.is_none_or(|s| s.has_node(node));
+        let way_in_scope = |way: &Way| scope.is_none_or(|s| s.has_way(way));
 
         // Look for footway=crossing ways that don't have crossing nodes on the roads
         for (_way_id, way) in &self.derived_ways {
@@ -20,7 +565,7 @@ impl Speedwalk {
             }
             for node_id in &way.node_ids {
                 let node = &self.derived_nodes[node_id];
-                if node.is_crossing() {
+                if node.is_crossing() || !node_in_scope(node) {
                     continue;
                 }
                 if node.way_ids.iter().any(|other_way_id| {
@@ -29,14 +574,17 @@ impl Speedwalk {
                         Kind::Sidewalk | Kind::Crossing | Kind::Other
                     )
                 }) {
-                    problem_nodes.push((*node_id, "missing crossing node", Vec::new()));
+                    problem_nodes.push((
+                        *node_id,
+                        Problem::new(ProblemType::MissingCrossingNode, Vec::new()),
+                    ));
                 }
             }
         }
 
         // Look for footways involving crossing nodes that aren't marked footway=crossing
         for (way_id, way) in &self.derived_ways {
-            if !matches!(way.kind, Kind::Sidewalk | Kind::Other) {
+            if !matches!(way.kind, Kind::Sidewalk | Kind::Other) || !way_in_scope(way) {
                 continue;
             }
 
@@ -57,30 +605,226 @@ impl Speedwalk {
             }
 
             if !detail_nodes.is_empty() {
-                problem_ways.push((*way_id, "missing footway=crossing", detail_nodes));
+                problem_ways.push((
+                    *way_id,
+                    Problem::new(ProblemType::MissingFootwayCrossing, detail_nodes),
+                ));
+            }
+        }
+
+        for (road_id, sidewalks) in self.find_parallel_sidewalks(scope) {
+            let road = &self.derived_ways[&road_id];
+
+            // Check each side's tag against the sidewalks found there
+            let mut tagged_no_sides = Vec::new();
+            for side in [Side::Left, Side::Right] {
+                let on_side: Vec<&ParallelSidewalk> =
+                    sidewalks.iter().filter(|x| x.side == side).collect();
+                match side_sidewalk_tag(&road.tags, side) {
+                    Some("no") if !on_side.is_empty() => {
+                        tagged_no_sides.push(side);
+                        let details = on_side.iter().flat_map(|x| x.details.clone()).collect();
+                        problem_ways.push((
+                            road_id,
+                            Problem {
+                                note: format!(
+                                    "no sidewalk tagged on the {} side, but a separate one seems to be there",
+                                    side.key()
+                                ),
+                                ..Problem::new(ProblemType::SidewalkTaggedNoButMapped, details)
+                            },
+                        ));
+                    }
+                    Some("separate") if on_side.is_empty() => {
+                        let mut details = Vec::new();
+                        let offset = match side {
+                            Side::Left => -SEPARATE_SIDEWALK_SEARCH,
+                            Side::Right => SEPARATE_SIDEWALK_SEARCH,
+                        };
+                        if let Some(ls) = road.linestring.offset_curve(offset) {
+                            let mut f = self.mercator.to_wgs84_gj(&ls);
+                            f.set_property("color", "red");
+                            f.set_property("label", "where a separate sidewalk was expected");
+                            details.push(f);
+                        }
+                        problem_ways.push((
+                            road_id,
+                            Problem {
+                                note: format!(
+                                    "separate sidewalk tagged on the {} side, but none was found there",
+                                    side.key()
+                                ),
+                                ..Problem::new(ProblemType::SeparateSidewalkNotFound, details)
+                            },
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+
+            // Sidewalks on a side tagged no already have a more specific problem
+            let untagged: Vec<&ParallelSidewalk> = sidewalks
+                .iter()
+                .filter(|x| !tagged_no_sides.contains(&x.side))
+                .collect();
+            // TODO Double check this
+            if matches!(
+                road.kind,
+                Kind::RoadWithTags
+                    | Kind::RoadWithoutSidewalksImplicit
+                    | Kind::RoadWithoutSidewalksExplicit
+                    | Kind::RoadUnknown
+            ) && !untagged.is_empty()
+            {
+                let details = untagged.iter().flat_map(|x| x.details.clone()).collect();
+                problem_ways.push((
+                    road_id,
+                    Problem::new(ProblemType::PossibleSeparateSidewalk, details),
+                ));
             }
         }
 
-        for (road, _sidewalks, details) in self.find_parallel_sidewalks() {
+        // Footways that may need to be split where they cross a road
+        let mut unsplit: Vec<(WayID, Vec<Feature>)> = Vec::new();
+        for (footway, road, pt) in self.find_unsplit_road_crossings(scope) {
+            let mut f = self.mercator.to_wgs84_gj(&Point::from(pt));
+            f.set_property("color", "red");
+            f.set_property("label", "crosses a road here");
+            f.set_property("road", road.0);
+            match unsplit.last_mut() {
+                Some((id, details)) if *id == footway => details.push(f),
+                _ => unsplit.push((footway, vec![f])),
+            }
+        }
+        for (footway, details) in unsplit {
             problem_ways.push((
-                road,
-                "possible separate sidewalk near way without it tagged",
-                details,
+                footway,
+                Problem::new(ProblemType::FootwayCrossesRoadWithoutNode, details),
             ));
         }
 
+        // Old crossing tagging schemes
+        for (node_id, node) in &self.derived_nodes {
+            if !node_in_scope(node) {
+                continue;
+            }
+            if let Some((notes, fix)) = deprecated_crossing_tags(&node.tags) {
+                problem_nodes.push((
+                    *node_id,
+                    Problem {
+                        note: notes.join(", "),
+                        tag_fix: Some(fix),
+                        ..Problem::new(ProblemType::DeprecatedCrossingTags, Vec::new())
+                    },
+                ));
+            }
+        }
         for (way_id, way) in &self.derived_ways {
-            if way.tags.is("sidewalk", "separate") {
+            if !way.tags.has("crossing") || !way_in_scope(way) {
+                continue;
+            }
+            if let Some((notes, fix)) = deprecated_crossing_tags(&way.tags) {
                 problem_ways.push((
                     *way_id,
-                    "sidewalk=separate is ambiguous about the side",
-                    Vec::new(),
+                    Problem {
+                        note: notes.join(", "),
+                        tag_fix: Some(fix),
+                        ..Problem::new(ProblemType::DeprecatedCrossingTags, Vec::new())
+                    },
+                ));
+            }
+        }
+        for x in self.find_inconsistent_crossings(scope) {
+            let mut f = self
+                .mercator
+                .to_wgs84_gj(&Point::from(self.derived_nodes[&x.node].pt));
+            f.set_property("color", "yellow");
+            f.set_property("label", format!("crossing node says {}", x.node_control));
+            problem_ways.push((
+                x.way,
+                Problem {
+                    note: format!(
+                        "the way says {}, but the crossing node says {}",
+                        x.way_control, x.node_control
+                    ),
+                    tag_fix: Some(x.fix),
+                    ..Problem::new(ProblemType::InconsistentCrossingTags, vec![f])
+                },
+            ));
+        }
+
+        // Sidewalks that end just short of connecting to something
+        let mut near_misses: Vec<(WayID, Vec<Feature>)> = Vec::new();
+        for near_miss in self.find_near_misses(scope) {
+            let mut f = self.mercator.to_wgs84_gj(&LineString::new(vec![
+                self.derived_nodes[&near_miss.node].pt,
+                near_miss.snapped,
+            ]));
+            f.set_property("color", "red");
+            f.set_property(
+                "label",
+                format!(
+                    "{:.1}m gap to way {}",
+                    near_miss.distance, near_miss.target.0
+                ),
+            );
+            match near_misses.last_mut() {
+                Some((id, details)) if *id == near_miss.way => details.push(f),
+                _ => near_misses.push((near_miss.way, vec![f])),
+            }
+        }
+        for (way, details) in near_misses {
+            problem_ways.push((way, Problem::new(ProblemType::NearMissEndpoint, details)));
+        }
+
+        for dup in self.find_duplicate_sidewalks(scope) {
+            let mut overlap = self.mercator.to_wgs84_gj(&dup.overlap);
+            overlap.set_property("color", "red");
+            overlap.set_property("label", "overlapping part");
+
+            for (id, other) in [
+                (dup.sidewalk1, dup.sidewalk2),
+                (dup.sidewalk2, dup.sidewalk1),
+            ] {
+                if !way_in_scope(&self.derived_ways[&id]) {
+                    continue;
+                }
+                let mut f = self
+                    .mercator
+                    .to_wgs84_gj(&self.derived_ways[&other].linestring);
+                f.set_property("color", "yellow");
+                f.set_property("label", "the other sidewalk");
+
+                let suggestion = match dup.suggestion(self, id) {
+                    DuplicateFix::Delete => "delete this generated one",
+                    DuplicateFix::Keep => "delete the generated one",
+                    DuplicateFix::Merge => "merge them",
+                };
+                problem_ways.push((
+                    id,
+                    Problem {
+                        note: format!(
+                            "overlaps sidewalk {} along {}% of the shorter one; {suggestion}",
+                            other.0,
+                            (dup.ratio * 100.0).round()
+                        ),
+                        ..Problem::new(ProblemType::DuplicateSidewalk, vec![overlap.clone(), f])
+                    },
                 ));
             }
         }
 
         for (way_id, way) in &self.derived_ways {
-            if way.kind != Kind::RoadWithSeparate {
+            if way.tags.is("sidewalk", "separate") && way_in_scope(way) {
+                problem_ways.push((
+                    *way_id,
+                    Problem::new(ProblemType::AmbiguousSidewalkSeparate, Vec::new()),
+                ));
+            }
+        }
+
+        for (way_id, way) in &self.derived_ways {
+            if way.kind != Kind::RoadWithSeparate || !way_in_scope(way) {
                 continue;
             }
             if way.tags.is_any("sidewalk:both", vec!["separate", "no"]) {
@@ -96,13 +840,15 @@ impl Speedwalk {
                 // TODO The description is literal, but maybe too verbose
                 problem_ways.push((
                     *way_id,
-                    "sidewalk:left and sidewalk:right should each be tagged as separate or no",
-                    Vec::new(),
+                    Problem::new(ProblemType::IncompleteSidewalkSides, Vec::new()),
                 ));
             }
         }
 
         for (node_id, node) in &self.derived_nodes {
+            if !node_in_scope(node) {
+                continue;
+            }
             let mut good_road_names = BTreeSet::new();
             let mut bad_road_names = BTreeSet::new();
             for w in &node.way_ids {
@@ -127,72 +873,115 @@ impl Speedwalk {
             {
                 problem_nodes.push((
                     *node_id,
-                    "separate sidewalks should be continued here",
-                    Vec::new(),
+                    Problem::new(ProblemType::SeparateSidewalksNotContinued, Vec::new()),
+                ));
+            }
+        }
+
+        // Crossing nodes on roads with separate sidewalks should be connected to them
+        for (node_id, outcome) in self.crossing_outcomes_near(&self.crossing_options, scope) {
+            let Some(problem_type) = outcome.problem_type() else {
+                continue;
+            };
+            if self.derived_nodes[&node_id]
+                .way_ids
+                .iter()
+                .any(|w| self.derived_ways[w].kind == Kind::RoadWithSeparate)
+            {
+                // The note says which side
+                problem_nodes.push((
+                    node_id,
+                    Problem {
+                        note: outcome.describe(),
+                        ..Problem::new(problem_type, Vec::new())
+                    },
                 ));
             }
         }
 
+        if self.check_accessibility {
+            for issue in self.find_accessibility_issues(scope) {
+                let mut problem = Problem::new(issue.problem_type, Vec::new());
+                for steps in issue.steps {
+                    let mut f = self
+                        .mercator
+                        .to_wgs84_gj(&self.derived_ways[&steps].linestring);
+                    f.set_property("color", "red");
+                    f.set_property("label", "steps");
+                    problem.details.push(f);
+                }
+                match issue.crossing {
+                    Crossing::Node(id) => problem_nodes.push((id, problem)),
+                    Crossing::Way(id) => problem_ways.push((id, problem)),
+                }
+            }
+        }
+
         // Fill out problems
-        for (id, note, details) in problem_nodes {
+        for (id, problem) in problem_nodes {
             self.derived_nodes
                 .get_mut(&id)
                 .unwrap()
                 .problems
-                .push(Problem {
-                    note: note.to_string(),
-                    details,
-                });
+                .push(problem);
         }
-        for (id, note, details) in problem_ways {
+        for (id, problem) in problem_ways {
             self.derived_ways
                 .get_mut(&id)
                 .unwrap()
                 .problems
-                .push(Problem {
-                    note: note.to_string(),
-                    details,
-                });
+                .push(problem);
         }
+        self.mark_suppressed_problems();
     }
 
-    // Returns pairs of (road, nearby matching sidewalks, debug)
-    fn find_parallel_sidewalks(&self) -> Vec<(WayID, Vec<WayID>, Vec<Feature>)> {
+    // Returns every road in scope, with the separate sidewalks that seem parallel to it
+    fn find_parallel_sidewalks(
+        &self,
+        scope: Option<&ProblemScope>,
+    ) -> Vec<(WayID, Vec<ParallelSidewalk>)> {
         let mut results = Vec::new();
 
-        let closest_way = RTree::bulk_load(
+        let mut roads: Vec<(&WayID, &Way)> = self
+            .derived_ways
+            .iter()
+            .filter(|(_, road)| road.kind.is_road() && scope.is_none_or(|s| s.has_way(road)))
+            .collect();
+        roads.sort_by_key(|(id, _)| **id);
+        // Only ways near the roads being checked matter
+        let Some(search) = roads
+            .iter()
+            .map(|(_, road)| buffer_aabb(aabb(&road.linestring), SEPARATE_SIDEWALK_SEARCH))
+            .reduce(|a, b| a.merged(&b))
+        else {
+            return results;
+        };
+        let nearby_ways = || {
             self.derived_ways
                 .iter()
+                .filter(move |(_, way)| search.intersects(&aabb(&way.linestring)))
+        };
+
+        let closest_way = RTree::bulk_load(
+            nearby_ways()
                 .map(|(id, way)| GeomWithData::new(way.linestring.clone(), *id))
                 .collect(),
         );
 
         let closest_sidewalk = RTree::bulk_load(
-            self.derived_ways
-                .iter()
+            nearby_ways()
                 .filter(|(_, way)| way.kind == Kind::Sidewalk)
                 .map(|(id, way)| GeomWithData::new(way.linestring.clone(), *id))
                 .collect(),
         );
 
-        for (road_id, road) in &self.derived_ways {
-            // TODO Double check this
-            if !matches!(
-                road.kind,
-                Kind::RoadWithTags
-                    | Kind::RoadWithoutSidewalksImplicit
-                    | Kind::RoadWithoutSidewalksExplicit
-                    | Kind::RoadUnknown
-            ) {
-                continue;
-            }
-
+        for (road_id, road) in roads {
+            let road_obj = GeomWithData::new(road.linestring.clone(), *road_id);
             let mut matching_sidewalks = Vec::new();
-            let mut details = Vec::new();
 
-            'SIDEWALK: for sidewalk in closest_sidewalk
-                .locate_in_envelope_intersecting(&buffer_aabb(aabb(&road.linestring), 15.0))
-            {
+            'SIDEWALK: for sidewalk in closest_sidewalk.locate_in_envelope_intersecting(
+                &buffer_aabb(aabb(&road.linestring), SEPARATE_SIDEWALK_SEARCH),
+            ) {
                 'LINE: for sidewalk_line in sidewalk.geom().lines() {
                     // Slice the candidate road by this one line segment in the sidewalk
                     if let Some((a, b)) =
@@ -230,7 +1019,7 @@ impl Speedwalk {
                             }
                         }
 
-                        matching_sidewalks.push(sidewalk.data);
+                        let mut details = Vec::new();
                         {
                             let mut f = self
                                 .mercator
@@ -245,14 +1034,16 @@ impl Speedwalk {
                             f.set_property("label", "straight line between the road and sidewalk that doesn't hit a building");
                             details.push(f);
                         }
+                        matching_sidewalks.push(ParallelSidewalk {
+                            side: classify_side(Point::from(midpt_line.0[0]), &road_obj),
+                            details,
+                        });
                         continue 'SIDEWALK;
                     }
                 }
             }
 
-            if !matching_sidewalks.is_empty() {
-                results.push((*road_id, matching_sidewalks, details));
-            }
+            results.push((*road_id, matching_sidewalks));
         }
 
         results
@@ -295,3 +1086,139 @@ fn angle_ls(ls: &LineString) -> f64 {
     // Ignore direction
     if a2 > 180.0 { a2 - 180.0 } else { a2 }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn test_problem_codes() {
+        let codes: HashSet<&str> = ProblemType::all().into_iter().map(|t| t.code()).collect();
+        assert_eq!(codes.len(), ProblemType::all().len());
+
+        let problem = Problem::new(ProblemType::MissingCrossingNode, Vec::new());
+        let json = serde_json::to_value(&problem).unwrap();
+        assert_eq!(json["code"], "missing_crossing_node");
+        assert_eq!(json["severity"], "error");
+        assert_eq!(json["category"], "tagging");
+        assert_eq!(json["note"], "missing crossing node");
+        assert!(json["fix"].is_string());
+        assert_eq!(json["suppressed"], false);
+    }
+
+    #[test]
+    fn test_suppressed_problems() {
+        let osm = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <node id="1" lon="0.000000" lat="0.000000" version="1" />
+  <node id="2" lon="0.000900" lat="0.000000" version="1" />
+  <way id="100" version="1">
+    <nd ref="1"/><nd ref="2"/>
+    <tag k="highway" v="residential"/>
+    <tag k="sidewalk" v="separate"/>
+  </way>
+</osm>"#;
+        let mut model = Speedwalk::new_from_osm(osm.as_bytes(), None).unwrap();
+        let suppressed = |model: &Speedwalk| {
+            model.derived_ways[&WayID(100)]
+                .problems
+                .iter()
+                .find(|p| p.problem_type == ProblemType::AmbiguousSidewalkSeparate)
+                .unwrap()
+                .suppressed
+        };
+        assert!(!suppressed(&model));
+
+        model.suppress_problem(ProblemSuppression {
+            element: "way/100".to_string(),
+            code: "ambiguous_sidewalk_separate".to_string(),
+        });
+        assert!(suppressed(&model));
+
+        // Still suppressed after problems are recalculated
+        model.after_edit();
+        assert!(suppressed(&model));
+
+        let exported = model.export_suppressed_problems().unwrap();
+        let mut fresh = Speedwalk::new_from_osm(osm.as_bytes(), None).unwrap();
+        fresh.import_suppressed_problems(&exported).unwrap();
+        assert!(suppressed(&fresh));
+    }
+
+    #[test]
+    fn test_side_tags_contradict_geometry() {
+        // The sidewalk is on the left of the road, but the tags say the opposite
+        let osm = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <node id="1" lon="0.000000" lat="0.000000" version="1" />
+  <node id="2" lon="0.000900" lat="0.000000" version="1" />
+  <node id="3" lon="0.000000" lat="0.000050" version="1" />
+  <node id="4" lon="0.000900" lat="0.000050" version="1" />
+  <way id="100" version="1">
+    <nd ref="1"/><nd ref="2"/>
+    <tag k="highway" v="residential"/>
+    <tag k="sidewalk:left" v="no"/>
+    <tag k="sidewalk:right" v="separate"/>
+  </way>
+  <way id="200" version="1">
+    <nd ref="3"/><nd ref="4"/>
+    <tag k="highway" v="footway"/>
+    <tag k="footway" v="sidewalk"/>
+  </way>
+</osm>"#;
+        let model = Speedwalk::new_from_osm(osm.as_bytes(), None).unwrap();
+        let problems = &model.derived_ways[&WayID(100)].problems;
+        let find = |problem_type| {
+            problems
+                .iter()
+                .find(|p| p.problem_type == problem_type)
+                .unwrap()
+        };
+
+        let no = find(ProblemType::SidewalkTaggedNoButMapped);
+        assert!(no.note.contains("left"));
+        assert!(!no.details.is_empty());
+        let missing = find(ProblemType::SeparateSidewalkNotFound);
+        assert!(missing.note.contains("right"));
+        assert_eq!(missing.details.len(), 1);
+        // South of the road
+        let geojson::Value::LineString(pts) = &missing.details[0].geometry.as_ref().unwrap().value
+        else {
+            panic!("expected a line");
+        };
+        assert!(pts.iter().all(|pt| pt[1] < 0.0));
+    }
+
+    #[test]
+    fn test_sidewalk_tagged_no_isnt_also_possible_separate() {
+        // The sidewalk is on the left of the road, where it's tagged no
+        let osm = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <node id="1" lon="0.000000" lat="0.000000" version="1" />
+  <node id="2" lon="0.000900" lat="0.000000" version="1" />
+  <node id="3" lon="0.000000" lat="0.000050" version="1" />
+  <node id="4" lon="0.000900" lat="0.000050" version="1" />
+  <way id="100" version="1">
+    <nd ref="1"/><nd ref="2"/>
+    <tag k="highway" v="residential"/>
+    <tag k="sidewalk:left" v="no"/>
+    <tag k="sidewalk:right" v="yes"/>
+  </way>
+  <way id="200" version="1">
+    <nd ref="3"/><nd ref="4"/>
+    <tag k="highway" v="footway"/>
+    <tag k="footway" v="sidewalk"/>
+  </way>
+</osm>"#;
+        let model = Speedwalk::new_from_osm(osm.as_bytes(), None).unwrap();
+        let types: Vec<ProblemType> = model.derived_ways[&WayID(100)]
+            .problems
+            .iter()
+            .map(|p| p.problem_type)
+            .collect();
+        assert!(types.contains(&ProblemType::SidewalkTaggedNoButMapped));
+        assert!(!types.contains(&ProblemType::PossibleSeparateSidewalk));
+    }
+}
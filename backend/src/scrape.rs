@@ -1,4 +1,4 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 
 use anyhow::Result;
 use geo::{ConvexHull, Coord, GeometryCollection, LineString, Polygon};
@@ -6,7 +6,7 @@ use osm_reader::{Element, OsmID};
 use rstar::RTree;
 use utils::{Mercator, Tags};
 
-use crate::{Edits, Kind, Node, Speedwalk, Way};
+use crate::{Edits, Kind, Node, Provenance, Speedwalk, Way, crossings::CrossingOptions};
 
 pub fn scrape_osm(input_bytes: &[u8], maybe_boundary_wgs84: Option<Polygon>) -> Result<Speedwalk> {
     let mut timestamp = None;
@@ -78,6 +78,7 @@ pub fn scrape_osm(input_bytes: &[u8], maybe_boundary_wgs84: Option<Polygon>) ->
                         linestring,
                         tags,
                         version: version.expect("way missing version"),
+                        provenance: Provenance::default(),
 
                         kind,
                         modified: false,
@@ -144,6 +145,9 @@ pub fn scrape_osm(input_bytes: &[u8], maybe_boundary_wgs84: Option<Polygon>) ->
         closest_building: RTree::bulk_load(buildings),
 
         edits: Some(Edits::default()),
+        suppressed_problems: BTreeSet::new(),
+        check_accessibility: false,
+        crossing_options: CrossingOptions::default(),
 
         derived_nodes: nodes,
         derived_ways: ways,
@@ -0,0 +1,180 @@
+use geo::{
+    Coord, Distance, Euclidean,
+    line_intersection::{LineIntersection, line_intersection},
+};
+use osm_reader::WayID;
+use rstar::{Envelope, RTree, primitives::GeomWithData};
+use utils::{Tags, aabb};
+
+use crate::{Kind, Speedwalk, Way, problems::ProblemScope};
+
+impl Speedwalk {
+    /// Footways that cross roads on the same level without a shared node. Returns (footway, road,
+    /// where they cross), sorted.
+    pub(crate) fn find_unsplit_road_crossings(
+        &self,
+        scope: Option<&ProblemScope>,
+    ) -> Vec<(WayID, WayID, Coord)> {
+        let footways: Vec<WayID> = self
+            .derived_ways
+            .iter()
+            .filter(|(_, way)| is_footway(way) && scope.is_none_or(|s| s.has_way(way)))
+            .map(|(id, _)| *id)
+            .collect();
+        self.unsplit_road_crossings(footways)
+    }
+
+    /// Where one footway crosses roads without a shared node, as (road, point), sorted
+    pub(crate) fn unsplit_road_crossings_on(&self, footway: WayID) -> Vec<(WayID, Coord)> {
+        self.unsplit_road_crossings(vec![footway])
+            .into_iter()
+            .map(|(_, road, pt)| (road, pt))
+            .collect()
+    }
+
+    fn unsplit_road_crossings(&self, mut footways: Vec<WayID>) -> Vec<(WayID, WayID, Coord)> {
+        footways.sort();
+        let Some(search) = footways
+            .iter()
+            .map(|id| aabb(&self.derived_ways[id].linestring))
+            .reduce(|a, b| a.merged(&b))
+        else {
+            return Vec::new();
+        };
+        let closest_road = RTree::bulk_load(
+            self.derived_ways
+                .iter()
+                .filter(|(_, way)| way.kind.is_road() && search.intersects(&aabb(&way.linestring)))
+                .map(|(id, way)| GeomWithData::new(way.linestring.clone(), *id))
+                .collect(),
+        );
+
+        let mut results = Vec::new();
+        for footway_id in footways {
+            let footway = &self.derived_ways[&footway_id];
+            let mut roads: Vec<WayID> = closest_road
+                .locate_in_envelope_intersecting(&aabb(&footway.linestring))
+                .map(|obj| obj.data)
+                .collect();
+            roads.sort();
+
+            for road_id in roads {
+                let road = &self.derived_ways[&road_id];
+                if level(&footway.tags) != level(&road.tags) {
+                    continue;
+                }
+                let shared_pts: Vec<Coord> = footway
+                    .node_ids
+                    .iter()
+                    .filter(|n| road.node_ids.contains(n))
+                    .map(|n| self.derived_nodes[n].pt)
+                    .collect();
+
+                let mut hits: Vec<Coord> = Vec::new();
+                for line1 in footway.linestring.lines() {
+                    for line2 in road.linestring.lines() {
+                        let Some(LineIntersection::SinglePoint { intersection, .. }) =
+                            line_intersection(line1, line2)
+                        else {
+                            continue;
+                        };
+                        // Hitting a vertex shows up on both lines touching it
+                        if shared_pts
+                            .iter()
+                            .chain(hits.iter())
+                            .any(|pt| Euclidean.distance(*pt, intersection) < 0.1)
+                        {
+                            continue;
+                        }
+                        hits.push(intersection);
+                    }
+                }
+                for pt in hits {
+                    results.push((footway_id, road_id, pt));
+                }
+            }
+        }
+        results
+    }
+}
+
+fn is_footway(way: &Way) -> bool {
+    way.kind == Kind::Sidewalk || way.is_walkable_other()
+}
+
+/// Roughly which vertical level a way is on. Ways on different levels don't need a shared node
+/// to cross.
+fn level(tags: &Tags) -> i64 {
+    if let Some(layer) = tags.get("layer").and_then(|x| x.parse().ok()) {
+        return layer;
+    }
+    if tags.has("bridge") && !tags.is("bridge", "no") {
+        1
+    } else if tags.has("tunnel") && !tags.is("tunnel", "no") {
+        -1
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ProblemType, UserCmd};
+
+    #[test]
+    fn test_unsplit_road_crossings() {
+        // Footway 200 crosses the road without a node, footway 201 is on a bridge
+        let osm = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="test">
+  <node id="1" lon="0.000000" lat="0.000000" version="1" />
+  <node id="2" lon="0.000900" lat="0.000000" version="1" />
+  <node id="3" lon="0.000300" lat="-0.000200" version="1" />
+  <node id="4" lon="0.000300" lat="0.000200" version="1" />
+  <node id="5" lon="0.000600" lat="-0.000200" version="1" />
+  <node id="6" lon="0.000600" lat="0.000200" version="1" />
+  <way id="100" version="1">
+    <nd ref="1"/><nd ref="2"/>
+    <tag k="highway" v="residential"/>
+  </way>
+  <way id="200" version="1">
+    <nd ref="3"/><nd ref="4"/>
+    <tag k="highway" v="footway"/>
+  </way>
+  <way id="201" version="1">
+    <nd ref="5"/><nd ref="6"/>
+    <tag k="highway" v="footway"/>
+    <tag k="bridge" v="yes"/>
+    <tag k="layer" v="1"/>
+  </way>
+</osm>"#;
+        let mut model = Speedwalk::new_from_osm(osm.as_bytes(), None).unwrap();
+        let found = model.find_unsplit_road_crossings(None);
+        assert_eq!(found.len(), 1);
+        assert_eq!((found[0].0, found[0].1), (WayID(200), WayID(100)));
+        let has_problem = |model: &Speedwalk, way: i64| {
+            model.derived_ways[&WayID(way)]
+                .problems
+                .iter()
+                .any(|p| p.problem_type == ProblemType::FootwayCrossesRoadWithoutNode)
+        };
+        assert!(has_problem(&model, 200));
+        assert!(!has_problem(&model, 201));
+
+        let mut edits = model.take_edits();
+        edits
+            .apply_cmd(UserCmd::SplitFootwayAtRoads(WayID(200)), &model)
+            .unwrap();
+        model.set_edits(edits);
+        model.after_edit();
+
+        assert!(model.find_unsplit_road_crossings(None).is_empty());
+        let footway = &model.derived_ways[&WayID(200)];
+        assert!(footway.problems.is_empty());
+        assert_eq!(footway.node_ids.len(), 3);
+        let shared = footway.node_ids[1];
+        assert!(model.derived_ways[&WayID(100)].node_ids.contains(&shared));
+        assert!(model.derived_nodes[&shared].tags.0.is_empty());
+        assert!(model.derived_nodes[&shared].problems.is_empty());
+    }
+}
@@ -9,7 +9,11 @@ use serde::{Deserialize, Serialize};
 use utils::{OffsetCurve, Tags};
 use wasm_bindgen::prelude::*;
 
-use crate::{Edits, Kind, Speedwalk, UserCmd};
+use crate::{
+    Edits, Kind, ProblemSuppression, ProblemType, Speedwalk, UserCmd,
+    accessibility::AccessibilityMetrics, crossings::CrossingOptions,
+    make_sidewalks::SidewalkOptions,
+};
 
 static START: Once = Once::new();
 
@@ -78,6 +82,52 @@ impl Speedwalk {
         )
     }
 
+    /// Every type of problem that can be detected, with its code, severity, category,
+    /// description, and suggested fix
+    #[wasm_bindgen(js_name = getProblemTypes)]
+    pub fn get_problem_types(&self) -> Result<String, JsValue> {
+        let types: Vec<_> = ProblemType::all().into_iter().map(|t| t.info()).collect();
+        serde_json::to_string(&types).map_err(err_to_js)
+    }
+
+    /// Mark a problem on an element (like `way/123`) as not an issue
+    #[wasm_bindgen(js_name = suppressProblem)]
+    pub fn suppress_problem_wasm(&mut self, element: String, code: String) {
+        self.suppress_problem(ProblemSuppression { element, code });
+    }
+
+    #[wasm_bindgen(js_name = unsuppressProblem)]
+    pub fn unsuppress_problem_wasm(&mut self, element: String, code: String) {
+        self.unsuppress_problem(&ProblemSuppression { element, code });
+    }
+
+    /// Turn the optional accessibility checks on crossings on or off
+    #[wasm_bindgen(js_name = setAccessibilityChecks)]
+    pub fn set_accessibility_checks_wasm(&mut self, enabled: bool) {
+        self.set_accessibility_checks(enabled);
+    }
+
+    #[wasm_bindgen(js_name = exportSuppressedProblems)]
+    pub fn export_suppressed_problems_wasm(&self) -> Result<String, JsValue> {
+        self.export_suppressed_problems().map_err(err_to_js)
+    }
+
+    #[wasm_bindgen(js_name = importSuppressedProblems)]
+    pub fn import_suppressed_problems_wasm(&mut self, input: String) -> Result<(), JsValue> {
+        self.import_suppressed_problems(&input).map_err(err_to_js)
+    }
+
+    /// MapRoulette challenges as line-delimited GeoJSON, in a JSON object keyed by group
+    #[wasm_bindgen(js_name = exportChallenges)]
+    pub fn export_challenges_wasm(&self, options: JsValue) -> Result<String, JsValue> {
+        let options: Option<crate::challenge::ChallengeOptions> =
+            serde_wasm_bindgen::from_value(options)?;
+        let challenges = self
+            .export_challenges(options.unwrap_or_default())
+            .map_err(err_to_js)?;
+        serde_json::to_string(&challenges).map_err(err_to_js)
+    }
+
     #[wasm_bindgen(js_name = getNodes)]
     pub fn get_nodes(&self) -> Result<String, JsValue> {
         let mut features = Vec::new();
@@ -128,6 +178,12 @@ impl Speedwalk {
             f.set_property("is_severance", way.is_severance());
             f.set_property("is_service", way.tags.is("highway", "service"));
             f.set_property("is_manual_crossing", way.tags.is("crossing", "manual"));
+            if let Some(road) = way.provenance.osm_way {
+                f.set_property("generated_from_way", road.0);
+            }
+            if let Some(side) = way.provenance.side {
+                f.set_property("generated_side", format!("{side:?}"));
+            }
             f.set_property(
                 "problems",
                 serde_json::to_value(&way.problems).map_err(err_to_js)?,
@@ -258,21 +314,79 @@ impl Speedwalk {
         Ok(serde_json::to_string(&GeoJson::from(features)).map_err(err_to_js)?)
     }
 
+    /// `options` is an optional `SidewalkOptions`; defaults are used when it's missing.
     #[wasm_bindgen(js_name = editMakeAllSidewalks)]
-    pub fn edit_make_all_sidewalks(&mut self, only_severances: bool) -> Result<(), JsValue> {
+    pub fn edit_make_all_sidewalks(
+        &mut self,
+        only_severances: bool,
+        options: JsValue,
+    ) -> Result<(), JsValue> {
+        let options: Option<SidewalkOptions> = serde_wasm_bindgen::from_value(options)?;
         let mut edits = self.edits.take().unwrap();
         // Ignore failure?
-        let _ = edits.apply_cmd(UserCmd::MakeAllSidewalks(only_severances), self);
+        let _ = edits.apply_cmd(
+            UserCmd::MakeAllSidewalks(only_severances, options.unwrap_or_default()),
+            self,
+        );
         self.edits = Some(edits);
         self.after_edit();
         Ok(())
     }
 
+    /// Returns GeoJSON describing everything `editMakeAllSidewalks` would do, without doing it.
+    /// New sidewalks have an `index` that can be passed to `editMakeSelectedSidewalks`.
+    #[wasm_bindgen(js_name = previewMakeAllSidewalks)]
+    pub fn preview_make_all_sidewalks_wasm(
+        &self,
+        only_severances: bool,
+        options: JsValue,
+    ) -> Result<String, JsValue> {
+        let options: Option<SidewalkOptions> = serde_wasm_bindgen::from_value(options)?;
+        self.preview_make_all_sidewalks(only_severances, &options.unwrap_or_default())
+            .map_err(err_to_js)
+    }
+
+    /// Only make the previewed sidewalks with these indices. `only_severances` and `options` must
+    /// match the preview.
+    #[wasm_bindgen(js_name = editMakeSelectedSidewalks)]
+    pub fn edit_make_selected_sidewalks(
+        &mut self,
+        only_severances: bool,
+        options: JsValue,
+        selected: JsValue,
+    ) -> Result<(), JsValue> {
+        let options: Option<SidewalkOptions> = serde_wasm_bindgen::from_value(options)?;
+        let selected: Vec<usize> = serde_wasm_bindgen::from_value(selected)?;
+        let mut edits = self.edits.take().unwrap();
+        edits
+            .apply_cmd(
+                UserCmd::MakeSelectedSidewalks(
+                    only_severances,
+                    options.unwrap_or_default(),
+                    selected,
+                ),
+                self,
+            )
+            .map_err(err_to_js)?;
+        self.edits = Some(edits);
+        self.after_edit();
+        Ok(())
+    }
+
+    /// `options` is an optional `CrossingOptions`; defaults are used when it's missing.
     #[wasm_bindgen(js_name = editConnectAllCrossings)]
-    pub fn edit_connect_all_crossings(&mut self, include_crossing_no: bool) -> Result<(), JsValue> {
+    pub fn edit_connect_all_crossings(
+        &mut self,
+        include_crossing_no: bool,
+        options: JsValue,
+    ) -> Result<(), JsValue> {
+        let options: Option<CrossingOptions> = serde_wasm_bindgen::from_value(options)?;
         let mut edits = self.edits.take().unwrap();
         // Ignore failure?
-        let _ = edits.apply_cmd(UserCmd::ConnectAllCrossings(include_crossing_no), self);
+        let _ = edits.apply_cmd(
+            UserCmd::ConnectAllCrossings(include_crossing_no, options.unwrap_or_default()),
+            self,
+        );
         self.edits = Some(edits);
         self.after_edit();
         Ok(())
@@ -316,6 +430,47 @@ impl Speedwalk {
         Ok(())
     }
 
+    #[wasm_bindgen(js_name = editSetNodeTags)]
+    pub fn edit_set_node_tags(
+        &mut self,
+        node: i64,
+        remove_keys: JsValue,
+        add_tags: JsValue,
+    ) -> Result<(), JsValue> {
+        let remove_keys: Vec<String> = serde_wasm_bindgen::from_value(remove_keys)?;
+        let add_tags: Vec<Vec<String>> = serde_wasm_bindgen::from_value(add_tags)?;
+        let mut edits = self.edits.take().unwrap();
+        edits
+            .apply_cmd(
+                UserCmd::SetNodeTags {
+                    node: NodeID(node),
+                    remove_keys,
+                    add_tags: add_tags
+                        .into_iter()
+                        .map(|mut kv| (kv.remove(0), kv.remove(0)))
+                        .collect(),
+                },
+                self,
+            )
+            .map_err(err_to_js)?;
+        self.edits = Some(edits);
+        self.after_edit();
+        Ok(())
+    }
+
+    /// Apply the suggested tags for every problem of this type that isn't suppressed
+    #[wasm_bindgen(js_name = editApplyTagFixes)]
+    pub fn edit_apply_tag_fixes(&mut self, code: String) -> Result<(), JsValue> {
+        let cmds = self.tag_fix_cmds(&code);
+        let mut edits = self.edits.take().unwrap();
+        edits
+            .apply_cmds_without_rebuild(cmds, self)
+            .map_err(err_to_js)?;
+        self.edits = Some(edits);
+        self.after_edit();
+        Ok(())
+    }
+
     // TODO Unused now, was just for debug
     #[wasm_bindgen(js_name = editAddNewCrossing)]
     pub fn edit_add_new_crossing(&mut self, x: f64, y: f64) -> Result<(), JsValue> {
@@ -363,7 +518,10 @@ impl Speedwalk {
         end_lng: f64,
         end_lat: f64,
         tags_js: JsValue,
+        options: JsValue,
     ) -> Result<(), JsValue> {
+        let options: Option<CrossingOptions> = serde_wasm_bindgen::from_value(options)?;
+        let options = options.unwrap_or_default();
         let tag_map: HashMap<String, String> = serde_wasm_bindgen::from_value(tags_js)?;
         let mut tags = Tags::empty();
         for (k, v) in tag_map {
@@ -373,7 +531,10 @@ impl Speedwalk {
         let end = Point::new(end_lng, end_lat);
         let mut edits = self.edits.take().unwrap();
         edits
-            .apply_cmd(UserCmd::AddCrossingSegment(start, end, tags), self)
+            .apply_cmd(
+                UserCmd::AddCrossingSegment(start, end, tags, options.max_snap_distance_meters),
+                self,
+            )
             .map_err(err_to_js)?;
         self.edits = Some(edits);
         self.after_edit();
@@ -389,11 +550,19 @@ impl Speedwalk {
         start_lat: f64,
         end_lng: f64,
         end_lat: f64,
+        options: JsValue,
     ) -> Result<String, JsValue> {
+        let options: Option<CrossingOptions> = serde_wasm_bindgen::from_value(options)?;
+        let options = options.unwrap_or_default();
         let start = Point::new(start_lng, start_lat);
         let end = Point::new(end_lng, end_lat);
-        let resolved =
-            crate::edits::resolve_crossing_segment(self, start, end).map_err(err_to_js)?;
+        let resolved = crate::edits::resolve_crossing_segment(
+            self,
+            start,
+            end,
+            options.max_snap_distance_meters,
+        )
+        .map_err(err_to_js)?;
         let out = serde_json::json!({
             "start": { "lat": resolved.start_lat, "lng": resolved.start_lng },
             "end": { "lat": resolved.end_lat, "lng": resolved.end_lng },
@@ -411,11 +580,19 @@ impl Speedwalk {
         start_lat: f64,
         end_lng: f64,
         end_lat: f64,
+        options: JsValue,
     ) -> Result<String, JsValue> {
+        let options: Option<CrossingOptions> = serde_wasm_bindgen::from_value(options)?;
+        let options = options.unwrap_or_default();
         let start = Point::new(start_lng, start_lat);
         let end = Point::new(end_lng, end_lat);
-        let resolved =
-            crate::edits::resolve_manual_deletion_edges(self, start, end).map_err(err_to_js)?;
+        let resolved = crate::edits::resolve_manual_deletion_edges(
+            self,
+            start,
+            end,
+            options.max_snap_distance_meters,
+        )
+        .map_err(err_to_js)?;
         serde_json::to_string(&serde_json::json!({ "edges": resolved })).map_err(err_to_js)
     }
 
@@ -442,13 +619,38 @@ impl Speedwalk {
         Ok(())
     }
 
+    #[wasm_bindgen(js_name = editSplitFootwayAtRoads)]
+    pub fn edit_split_footway_at_roads(&mut self, footway: i64) -> Result<(), JsValue> {
+        let mut edits = self.edits.take().unwrap();
+        edits
+            .apply_cmd(UserCmd::SplitFootwayAtRoads(WayID(footway)), self)
+            .map_err(err_to_js)?;
+        self.edits = Some(edits);
+        self.after_edit();
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = editSnapEndpoints)]
+    pub fn edit_snap_endpoints(&mut self, way: i64) -> Result<(), JsValue> {
+        let mut edits = self.edits.take().unwrap();
+        edits
+            .apply_cmd(UserCmd::SnapEndpoints(WayID(way)), self)
+            .map_err(err_to_js)?;
+        self.edits = Some(edits);
+        self.after_edit();
+        Ok(())
+    }
+
     /// Apply many manual overrides in one call. This batches commands and rebuilds derived state once.
     #[wasm_bindgen(js_name = editApplyManualOverridesBatch)]
     pub fn edit_apply_manual_overrides_batch(
         &mut self,
         crossings_js: JsValue,
         deletions_js: JsValue,
+        options: JsValue,
     ) -> Result<(), JsValue> {
+        let options: Option<CrossingOptions> = serde_wasm_bindgen::from_value(options)?;
+        let options = options.unwrap_or_default();
         let crossings: Vec<BatchCrossingInput> = serde_wasm_bindgen::from_value(crossings_js)?;
         let deletions: Vec<BatchDeletionInput> = serde_wasm_bindgen::from_value(deletions_js)?;
         let mut cmds = Vec::with_capacity(crossings.len() + deletions.len());
@@ -471,6 +673,7 @@ impl Speedwalk {
                     Point::new(crossing.start.lng, crossing.start.lat),
                     Point::new(crossing.end.lng, crossing.end.lat),
                     tags,
+                    options.max_snap_distance_meters,
                 ));
             }
         }
@@ -505,7 +708,7 @@ impl Speedwalk {
         cmds.retain(|cmd| {
             !matches!(
                 cmd,
-                UserCmd::AddCrossingSegment(_, _, _)
+                UserCmd::AddCrossingSegment(..)
                     | UserCmd::AddCrossingSegmentSnapped { .. }
                     | UserCmd::ManualDeleteEdge { .. }
             )
@@ -572,6 +775,20 @@ impl Speedwalk {
         self.audit_crossings(options).map_err(err_to_js)
     }
 
+    #[wasm_bindgen(js_name = auditCrossingsSummary)]
+    pub fn audit_crossings_summary_wasm(&self, options: JsValue) -> Result<String, JsValue> {
+        let options: crate::audit::Options = serde_wasm_bindgen::from_value(options)?;
+        self.audit_crossings_summary(options).map_err(err_to_js)
+    }
+
+    #[wasm_bindgen(js_name = auditCrossingSpacing)]
+    pub fn audit_crossing_spacing_wasm(&self, options: JsValue) -> Result<String, JsValue> {
+        let options: Option<crate::crossing_spacing::SpacingOptions> =
+            serde_wasm_bindgen::from_value(options)?;
+        self.audit_crossing_spacing(options.unwrap_or_default())
+            .map_err(err_to_js)
+    }
+
     #[wasm_bindgen(js_name = editGenerateMissingCrossings)]
     pub fn edit_generate_missing_crossings_wasm(
         &mut self,
@@ -581,6 +798,30 @@ impl Speedwalk {
         self.generate_missing_crossings(options).map_err(err_to_js)
     }
 
+    /// Creates crossing ways between sidewalk corners on junction arms without crossings.
+    /// `crossing_value` tags the new crossings, defaulting to `unknown`.
+    #[wasm_bindgen(js_name = editConnectJunctionCorners)]
+    pub fn edit_connect_junction_corners(
+        &mut self,
+        options: JsValue,
+        crossing_value: Option<String>,
+    ) -> Result<(), JsValue> {
+        let options: crate::audit::Options = serde_wasm_bindgen::from_value(options)?;
+        let mut edits = self.edits.take().unwrap();
+        edits
+            .apply_cmd(
+                UserCmd::ConnectJunctionCorners(
+                    options,
+                    crossing_value.unwrap_or_else(|| "unknown".to_string()),
+                ),
+                self,
+            )
+            .map_err(err_to_js)?;
+        self.edits = Some(edits);
+        self.after_edit();
+        Ok(())
+    }
+
     #[wasm_bindgen(js_name = findConnectedComponents)]
     pub fn find_connected_components_wasm(&self, filter: JsValue) -> Result<String, JsValue> {
         let graph = crate::graph::Graph::new(self);
@@ -691,6 +932,9 @@ mod tests {
 #[derive(Default, Serialize)]
 struct Metrics {
     total_length_meters: BTreeMap<Kind, f64>,
+    /// Only when the accessibility checks are on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    accessibility: Option<AccessibilityMetrics>,
 }
 
 impl Metrics {
@@ -703,6 +947,9 @@ impl Metrics {
             *metrics.total_length_meters.get_mut(&way.kind).unwrap() +=
                 Euclidean.length(&way.linestring);
         }
+        if model.check_accessibility {
+            metrics.accessibility = Some(model.accessibility_metrics());
+        }
         metrics
     }
 }
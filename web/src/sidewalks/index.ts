@@ -35,12 +35,33 @@ export interface WayProps {
   is_severance: boolean;
   is_service: boolean;
   is_manual_crossing?: boolean;
+  generated_from_way?: number;
+  generated_side?: "Left" | "Right";
   problems: Problem[];
 }
 
-interface Problem {
+export interface Problem {
+  code: string;
+  severity: "error" | "warning" | "info";
+  category: "tagging" | "topology" | "geometry" | "accessibility";
+  fix: string;
   note: string;
   details: Feature[];
+  suppressed: boolean;
+  tag_fix?: TagFix;
+}
+
+export interface TagFix {
+  remove_keys: string[];
+  add_tags: Array<[string, string]>;
+}
+
+export interface ProblemType {
+  code: string;
+  severity: "error" | "warning" | "info";
+  category: "tagging" | "topology" | "geometry" | "accessibility";
+  description: string;
+  fix: string;
 }
 
 export let colors = {
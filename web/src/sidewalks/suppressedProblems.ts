@@ -0,0 +1,54 @@
+import { get } from "svelte/store";
+import { localStorageStore } from "svelte-utils";
+import { backend, mutationCounter } from "../";
+
+// Problems marked as not an issue, as exported by the backend, keyed by study area
+let saved = localStorageStore<Record<string, string>>(
+  "speedwalk-suppressedProblems",
+  {},
+);
+
+// A short hash of the boundary identifies the study area
+function studyAreaKey(): string {
+  let boundary = get(backend)!.getBoundary();
+  let hash = 5381;
+  for (let i = 0; i < boundary.length; i++) {
+    hash = ((hash << 5) + hash + boundary.charCodeAt(i)) | 0;
+  }
+  return (hash >>> 0).toString(16);
+}
+
+function save() {
+  let key = studyAreaKey();
+  let value = get(backend)!.exportSuppressedProblems();
+  saved.update((all) => ({ ...all, [key]: value }));
+}
+
+/** Restore the suppressions saved for the study area that just loaded */
+export function loadSuppressedProblems() {
+  let value = get(saved)[studyAreaKey()];
+  if (value) {
+    get(backend)!.importSuppressedProblems(value);
+    mutationCounter.update((n) => n + 1);
+  }
+}
+
+/** `element` is like `way/123` or `node/456` */
+export function suppressProblem(element: string, code: string) {
+  get(backend)!.suppressProblem(element, code);
+  save();
+  mutationCounter.update((n) => n + 1);
+}
+
+export function unsuppressProblem(element: string, code: string) {
+  get(backend)!.unsuppressProblem(element, code);
+  save();
+  mutationCounter.update((n) => n + 1);
+}
+
+/** Adds to the suppressions already there. Throws if the input is bad. */
+export function importSuppressedProblems(input: string) {
+  get(backend)!.importSuppressedProblems(input);
+  save();
+  mutationCounter.update((n) => n + 1);
+}